@@ -1,8 +1,42 @@
+use aarch64_cpu::{asm, registers::*};
 use core::arch::global_asm;
 
 global_asm!(include_str!("boot.s"));
 
+extern "C" {
+    // Written by _start before anything else runs
+    static adr_dtb: usize;
+}
+
+// Firmware starts kernel in EL2, exception vectors and interruptions
+// are set up for EL1, so kernel_init is entered there
+unsafe fn prepare_el2_to_el1_transition(stack_end: u64) {
+    // Timer counters stay accessible from EL1
+    CNTHCTL_EL2.write(CNTHCTL_EL2::EL1PCEN::SET + CNTHCTL_EL2::EL1PCTEN::SET);
+    CNTVOFF_EL2.set(0);
+    HCR_EL2.write(HCR_EL2::RW::EL1IsAarch64);
+    // Everything stays masked until exception handling is set up
+    SPSR_EL2.write(
+        SPSR_EL2::D::Masked
+            + SPSR_EL2::A::Masked
+            + SPSR_EL2::I::Masked
+            + SPSR_EL2::F::Masked
+            + SPSR_EL2::M::EL1h,
+    );
+    ELR_EL2.set(crate::kernel_init as *const () as u64);
+    SP_EL1.set(stack_end);
+}
+
 #[no_mangle]
-pub unsafe fn _start_rust() -> () {
+pub unsafe extern "C" fn _start_rust(stack_end: u64) -> ! {
+    if CurrentEL.read_as_enum(CurrentEL::EL) == Some(CurrentEL::EL::Value::EL2) {
+        prepare_el2_to_el1_transition(stack_end);
+        asm::eret()
+    }
     crate::kernel_init()
 }
+
+// Device tree blob firmware passed in x0, 0 when there was none
+pub fn dtb_address() -> usize {
+    unsafe { core::ptr::read_volatile(core::ptr::addr_of!(adr_dtb)) }
+}
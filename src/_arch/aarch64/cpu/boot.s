@@ -5,8 +5,6 @@
 
 .section .text._start
 
-// Vector table here for ARM from exceptions.s
-
 _start:
   // Change DTB pointer position from x1 to x10 for future usage by kernel
   ldr x1, =adr_dtb
@@ -45,4 +43,7 @@ _start:
 .type _start, function
 .global _start
 
-adr_dtb: .quad
+.section .data
+.balign 8
+.global adr_dtb
+adr_dtb: .quad 0
@@ -0,0 +1,30 @@
+use aarch64_cpu::{asm::barrier, registers::*};
+use core::arch::{asm, global_asm};
+
+global_asm!(include_str!("exceptions.s"));
+
+extern "C" {
+    static __exception_vector_start: u8;
+}
+
+#[no_mangle]
+#[link_section = ".text.handlers"]
+unsafe extern "C" fn unhandled_exception() -> ! {
+    panic!(
+        "Unhandled exception, ESR_EL1: {:#x}, ELR_EL1: {:#x}",
+        ESR_EL1.get(),
+        ELR_EL1.get()
+    );
+}
+
+// Vector table must be in place before any interruption gets unmasked
+pub unsafe fn init_exception_handling() {
+    VBAR_EL1.set(core::ptr::addr_of!(__exception_vector_start) as u64);
+    // New vector base must be seen before next exception is taken
+    barrier::isb(barrier::SY);
+}
+
+pub unsafe fn unmask_irq() {
+    // Clears DAIF.I only, other masks stay as they are
+    asm!("msr DAIFClr, #2", options(nomem, nostack));
+}
@@ -1,62 +1,76 @@
+// Saves registers handler may clobber together with return state,
+// so exception can be returned from with eret
+.macro CALL_WITH_CONTEXT handler
+.balign 0x80
+	sub sp, sp, #16 * 12
+	stp x0, x1, [sp, #16 * 0]
+	stp x2, x3, [sp, #16 * 1]
+	stp x4, x5, [sp, #16 * 2]
+	stp x6, x7, [sp, #16 * 3]
+	stp x8, x9, [sp, #16 * 4]
+	stp x10, x11, [sp, #16 * 5]
+	stp x12, x13, [sp, #16 * 6]
+	stp x14, x15, [sp, #16 * 7]
+	stp x16, x17, [sp, #16 * 8]
+	stp x18, x29, [sp, #16 * 9]
+	mrs x1, ELR_EL1
+	mrs x2, SPSR_EL1
+	stp x30, x1, [sp, #16 * 10]
+	str x2, [sp, #16 * 11]
+	bl \handler
+	b __exception_restore_context
+.endm
+
 .section .text.vector_table
 
-vector_table:
-    b _start
-    b _reset
-    b _reset
-    b _reset
-    b _reset
-    b _reset
-    b irq_handler
-    b fiq_handler
+// 16 entries 0x80 apart, VBAR_EL1 needs 2 KiB alignment
+.balign 0x800
+.global __exception_vector_start
+__exception_vector_start:
+
+// Current EL with SP_EL0, kernel never runs on it
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+
+// Current EL with SP_ELx
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT irq_handler
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
 
+// Lower EL, AArch64
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+
+// Lower EL, AArch32
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
+CALL_WITH_CONTEXT unhandled_exception
 
 .section .text.handlers
 
-prehandler:
-	stp x0, x1, [sp, #-16]!
-	stp x2, x3, [sp, #-16]!
-	stp x4, x5, [sp, #-16]!
-	stp x6, x7, [sp, #-16]!
-	stp x8, x9, [sp, #-16]!
-	stp x10, x11, [sp, #-16]!
-	stp x12, x13, [sp, #-16]!
-	stp x14, x15, [sp, #-16]!
-	stp x16, x17, [sp, #-16]!
-	stp x18, x19, [sp, #-16]!
-	stp x20, x21, [sp, #-16]!
-	stp x22, x23, [sp, #-16]!
-	stp x24, x25, [sp, #-16]!
-	stp x26, x27, [sp, #-16]!
-	stp x28, x29, [sp, #-16]!
-	bl identify_and_clear_source
-	bl irq_handler
-	ldp x28, x29, [sp], #16
-	ldp x26, x27, [sp], #16
-	ldp x24, x25, [sp], #16
-	ldp x22, x23, [sp], #16
-	ldp x20, x21, [sp], #16
-	ldp x20, x21, [sp], #16
-	ldp x18, x19, [sp], #16
-	ldp x16, x17, [sp], #16
-	ldp x14, x15, [sp], #16
-	ldp x12, x13, [sp], #16
-	ldp x10, x11, [sp], #16
-	ldp x8, x9, [sp], #16
-	ldp x6, x7, [sp], #16
-	ldp x4, x5, [sp], #16
-	ldp x2, x3, [sp], #16
-	ldp x0, x1, [sp], #16
-	
-
-
-
-identify_and_clear_source:
-	br x30
-
-
-_reset: 
-	b _start
-
-fiq_handler:
-  b _start
+__exception_restore_context:
+	ldr x2, [sp, #16 * 11]
+	ldp x30, x1, [sp, #16 * 10]
+	msr SPSR_EL1, x2
+	msr ELR_EL1, x1
+	ldp x18, x29, [sp, #16 * 9]
+	ldp x16, x17, [sp, #16 * 8]
+	ldp x14, x15, [sp, #16 * 7]
+	ldp x12, x13, [sp, #16 * 6]
+	ldp x10, x11, [sp, #16 * 5]
+	ldp x8, x9, [sp, #16 * 4]
+	ldp x6, x7, [sp, #16 * 3]
+	ldp x4, x5, [sp, #16 * 2]
+	ldp x2, x3, [sp, #16 * 1]
+	ldp x0, x1, [sp, #16 * 0]
+	add sp, sp, #16 * 12
+	eret
+
+.size __exception_restore_context, . - __exception_restore_context
+.type __exception_restore_context, function
@@ -0,0 +1,35 @@
+use aarch64_cpu::{asm::barrier, registers::*};
+use core::time::Duration;
+
+const NANOSEC_PER_SEC: u64 = 1_000_000_000;
+
+fn counter_frequency() -> u64 {
+    CNTFRQ_EL0.get()
+}
+
+fn read_counter() -> u64 {
+    // Prevent counter read from being executed speculatively ahead of time
+    barrier::isb(barrier::SY);
+    CNTPCT_EL0.get()
+}
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    let frequency = counter_frequency();
+    let secs = ticks / frequency;
+    let sub_second_ticks = ticks % frequency;
+    let nanos = (sub_second_ticks * NANOSEC_PER_SEC / frequency) as u32;
+    Duration::new(secs, nanos)
+}
+
+pub fn uptime() -> Duration {
+    ticks_to_duration(read_counter())
+}
+
+pub fn spin_for(duration: Duration) {
+    let frequency = counter_frequency() as u128;
+    let ticks = (duration.as_nanos() * frequency / NANOSEC_PER_SEC as u128) as u64;
+    let start = read_counter();
+    while read_counter().wrapping_sub(start) < ticks {
+        core::hint::spin_loop()
+    }
+}
@@ -1,2 +1,10 @@
 pub mod bcm;
+pub mod bitbang;
+pub mod bme280;
+pub mod bus;
 pub mod common;
+pub mod ds_rtc;
+pub mod eeprom24x;
+#[cfg(feature = "embedded-hal")]
+pub mod hal;
+pub mod mcp9808;
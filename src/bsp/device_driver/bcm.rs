@@ -1,50 +1,298 @@
+pub mod bcm2711_aux;
+pub mod bcm2711_bsc_slave;
+pub mod bcm2711_cm;
+pub mod bcm2711_framebuffer;
 pub mod bcm2711_gpio;
 pub mod bcm2711_i2c;
 pub mod bcm2711_irq;
+pub mod bcm2711_mailbox;
+pub mod bcm2711_pwm;
+pub mod bcm2711_spi;
 pub mod bcm2711_uart;
 
 use crate::bsp::{
-    bcm::bcm2711_gpio::{GPIODriver, GPIOFunction, PullResistor},
-    console::register_console,
+    bus::SpiMode,
+    bcm::bcm2711_gpio::{set_gpio_base, GPIOError, GpioController},
+    console::{register_console, register_console_sink},
 };
+use crate::bsp::{
+    bme280::{Bme280, BME280_ADDRESSES},
+    ds_rtc::{DsRtc, DsRtcModel},
+    mcp9808::{Mcp9808, MCP9808_ADDRESSES},
+};
+use crate::device_tree::{device_tree, for_each_compatible, DeviceNode};
+use crate::driver::{
+    driver_manager, DeviceDriverDescriptor, DeviceTreeMatch, DriverError, PostInitCallback,
+};
+use crate::sensors::register_sensor;
+use fdt::Fdt;
+use bcm2711_framebuffer::{Framebuffer, FramebufferConsole};
+pub use bcm2711_aux::{AuxSpi, AuxSpiController};
+pub use bcm2711_bsc_slave::{BscSlave, RegisterFile, SlaveMode};
+pub use bcm2711_cm::ClockManager;
 pub use bcm2711_i2c::*;
+pub use bcm2711_mailbox::Mailbox;
+pub use bcm2711_pwm::{Pwm, PwmController, PwmError};
+pub use bcm2711_spi::{Spi, SpiController, SPI_CONTROLLERS};
 pub use bcm2711_uart::*;
 
 use crate::synchronization::interface::Mutex;
-static mut UART_MANAGER: DriverManager<Uart> = DriverManager(None);
-static mut I2C_MANAGER: DriverManager<I2C> = DriverManager(None);
+static mut I2C_BUSES: [I2C; 6] = [
+    I2C::new(I2cController::I2c0, 100_000, 3),
+    I2C::new(I2cController::I2c1, 100_000, 3),
+    I2C::new(I2cController::I2c3, 100_000, 3),
+    I2C::new(I2cController::I2c4, 100_000, 3),
+    I2C::new(I2cController::I2c5, 100_000, 3),
+    I2C::new(I2cController::I2c6, 100_000, 3),
+];
+// Buses by number, only initialized ones are here
+static mut I2C_REGISTRY: [Option<&'static I2C>; 7] = [None; 7];
+// Device tree may move it to where its first PL011 node points
+static mut UART: Uart = console_uart(0x0_FE20_1000);
+static GPIO_CONTROLLER: GpioController = GpioController;
+static mut SPI_BUSES: [Spi; 5] = [
+    Spi::new(SpiController::Spi0, SpiMode::Mode0, 1_000_000),
+    Spi::new(SpiController::Spi3, SpiMode::Mode0, 1_000_000),
+    Spi::new(SpiController::Spi4, SpiMode::Mode0, 1_000_000),
+    Spi::new(SpiController::Spi5, SpiMode::Mode0, 1_000_000),
+    Spi::new(SpiController::Spi6, SpiMode::Mode0, 1_000_000),
+];
+// Buses in SPI_CONTROLLERS order, only initialized ones are here
+static mut SPI_REGISTRY: [Option<&'static Spi>; 5] = [None; 5];
+static mut CLOCK_MANAGER: ClockManager = ClockManager::new(0x0_FE10_1000);
+static mut MAILBOX: Mailbox = Mailbox::new(0x0_FE00_B880);
+static mut PWM0: Pwm = Pwm::new(PwmController::Pwm0);
+static mut PWM1: Pwm = Pwm::new(PwmController::Pwm1);
+// Enabled on demand, shares pins with SPI0
+static mut BSC_SLAVE: BscSlave = BscSlave::new(SlaveMode::I2c { address: 0x42 });
+// Brought up only when device tree enables them, their pins overlap
+// PWM and SPI6 routing
+static mut AUX_SPI1: AuxSpi = AuxSpi::new(AuxSpiController::Spi1, SpiMode::Mode0, 1_000_000);
+static mut AUX_SPI2: AuxSpi = AuxSpi::new(AuxSpiController::Spi2, SpiMode::Mode0, 1_000_000);
+static mut FRAMEBUFFER_CONSOLE: Option<FramebufferConsole> = None;
+// Sensors found on BSC1 at boot
+static mut BME280: Option<Bme280<'static, I2C>> = None;
+static mut MCP9808: Option<Mcp9808<'static, I2C>> = None;
+// DS1307 boards answer at the same address, set model to match
+const RTC_MODEL: DsRtcModel = DsRtcModel::Ds3231;
+static mut RTC: Option<DsRtc<'static, I2C>> = None;
+
+// Nodes are matched in this order, which is init order as well
+static DEVICE_TREE_MATCHES: [DeviceTreeMatch; 5] = [
+    DeviceTreeMatch {
+        compatible: "brcm,bcm2711-gpio",
+        probe: probe_gpio,
+    },
+    DeviceTreeMatch {
+        compatible: "brcm,bcm2711-i2c",
+        probe: probe_i2c,
+    },
+    DeviceTreeMatch {
+        compatible: "brcm,bcm2835-spi",
+        probe: probe_spi,
+    },
+    DeviceTreeMatch {
+        compatible: "brcm,bcm2835-pwm",
+        probe: probe_pwm,
+    },
+    DeviceTreeMatch {
+        compatible: "brcm,bcm2835-aux-spi",
+        probe: probe_aux_spi,
+    },
+];
+const I2C_POST_INIT: [PostInitCallback; 6] = [
+    post_init_i2c::<0>,
+    post_init_i2c::<1>,
+    post_init_i2c::<2>,
+    post_init_i2c::<3>,
+    post_init_i2c::<4>,
+    post_init_i2c::<5>,
+];
+const SPI_POST_INIT: [PostInitCallback; 5] = [
+    post_init_spi::<0>,
+    post_init_spi::<1>,
+    post_init_spi::<2>,
+    post_init_spi::<3>,
+    post_init_spi::<4>,
+];
+
+const fn console_uart(base: usize) -> Uart {
+    unsafe { Uart::new(base, ParityBit::None, WordLength::Bit8, StopBits::One, 9600) }
+}
+
 pub unsafe fn init_drivers() {
-    let mut GPIO14: GPIODriver =
-        unsafe { GPIODriver::new(14, GPIOFunction::Alt0, PullResistor::Up) };
-    let mut GPIO15: GPIODriver =
-        unsafe { GPIODriver::new(15, GPIOFunction::Alt0, PullResistor::Up) };
-    GPIO14.init();
-    GPIO15.init();
-    let uart_manager = uart_manager();
-    // UART SECTION
-    static mut UART: Uart = unsafe {
-        Uart::new(
-            0x0_FE20_1000,
-            ParityBit::None,
-            WordLength::Bit8,
-            StopBits::One,
-            9600,
-        )
+    bcm2711_irq::init_interrupt_controller();
+    let manager = driver_manager();
+    let tree = device_tree();
+    if let Ok(fdt) = &tree {
+        probe_console_uart(fdt);
+    }
+    // Console goes first, so the rest can report how their init went.
+    // Rejected drivers are reported by init_drivers
+    let _ = manager.register_driver(DeviceDriverDescriptor::new(&UART, None));
+    // Without device tree BSC1, the one on header pins 3/5, is the only
+    // bus brought up, other ones are enabled on demand
+    let probed = match &tree {
+        Ok(fdt) => manager.probe_device_tree(fdt, &DEVICE_TREE_MATCHES),
+        Err(_) => {
+            for descriptor in [
+                DeviceDriverDescriptor::new(&GPIO_CONTROLLER, None),
+                DeviceDriverDescriptor::new(&I2C_BUSES[1], Some(I2C_POST_INIT[1])),
+                DeviceDriverDescriptor::new(&SPI_BUSES[0], Some(SPI_POST_INIT[0])),
+                DeviceDriverDescriptor::new(&PWM0, None),
+                DeviceDriverDescriptor::new(&PWM1, None),
+            ] {
+                let _ = manager.register_driver(descriptor);
+            }
+            0
+        }
     };
-    uart_manager.register_driver(&mut UART);
+    // UART claims its pins in init, after device tree moved GPIO block
     register_console(&mut UART);
-    uart_manager.init_drivers();
-    // GPIO SECTION
-    let mut GPIO2: GPIODriver = unsafe { GPIODriver::new(2, GPIOFunction::Alt0, PullResistor::Up) };
-    GPIO2.init();
-    let mut GPIO3: GPIODriver = unsafe { GPIODriver::new(3, GPIOFunction::Alt0, PullResistor::Up) };
-    GPIO3.init();
-    // I2C Section
-    let i2c_manager = i2c_manager();
-    static mut I2C: I2C = I2C::new(0x0_FE80_4000, 100_000, 3);
-    i2c_manager.register_driver(&mut I2C);
-    i2c_manager.init_drivers();
-    crate::println!("Drivers initialized successfully!\n");
+    let failed = manager.init_drivers();
+    match tree {
+        Ok(_) => crate::println!("{} devices probed from device tree", probed),
+        Err(error) => crate::println!("No device tree, using built-in drivers: {}", error),
+    }
+    // FRAMEBUFFER SECTION
+    // Display is optional, without it kernel keeps only uart console
+    match Framebuffer::allocate(0, 0) {
+        Ok(framebuffer) => {
+            FRAMEBUFFER_CONSOLE = Some(FramebufferConsole::new(framebuffer));
+            if let Some(console) = &FRAMEBUFFER_CONSOLE {
+                register_console_sink(console);
+            }
+        }
+        Err(error) => crate::println!("Framebuffer not available: {}", error),
+    }
+    // Sensors and RTC are looked for only when the header bus came up,
+    // device tree may leave it disabled
+    if let Some(i2c1) = i2c_bus(1) {
+        probe_i2c1_devices(i2c1);
+    }
+    match failed {
+        0 => crate::println!("Drivers initialized successfully!"),
+        _ => crate::println!("{} drivers failed to initialize", failed),
+    }
+}
+
+unsafe fn probe_i2c1_devices(i2c1: &'static I2C) {
+    // SENSOR Section
+    // Only first chip of each kind on the header bus is picked up
+    BME280 = BME280_ADDRESSES
+        .iter()
+        .find_map(|address| Bme280::probe(i2c1, *address).ok());
+    MCP9808 = MCP9808_ADDRESSES
+        .iter()
+        .find_map(|address| Mcp9808::probe(i2c1, *address).ok());
+    if let Some(sensor) = &BME280 {
+        register_sensor(sensor);
+    }
+    if let Some(sensor) = &MCP9808 {
+        register_sensor(sensor);
+    }
+    // RTC Section
+    // Without RTC wall clock stays unset until date -s
+    RTC = DsRtc::probe(i2c1, RTC_MODEL).ok();
+    if let Some(rtc) = &RTC {
+        if let Err(error) = crate::time::register_rtc(rtc) {
+            crate::println!("RTC time not read: {}", error);
+        }
+    }
+}
+
+unsafe fn post_init_i2c<const N: usize>() -> Result<(), DriverError> {
+    register_i2c_bus(&I2C_BUSES[N]);
+    Ok(())
+}
+
+unsafe fn post_init_spi<const N: usize>() -> Result<(), DriverError> {
+    register_spi_bus(&SPI_BUSES[N]);
+    Ok(())
+}
+
+//_____________________________________________________________
+//
+//  DEVICE TREE PROBING
+//_________________________________________
+//
+// Interruption given in tree, if it is a VideoCore one
+fn vc_irq(node: &DeviceNode) -> Option<u32> {
+    node.interrupt.and_then(bcm2711_irq::vc_irq_from_gic)
+}
+
+// Console UART is set up from the first PL011 node before it is
+// registered, kernel has no instances for the rest. Its clock stays
+// with firmware, tree rate is the fallback only
+unsafe fn probe_console_uart(fdt: &Fdt) {
+    let mut probed = false;
+    for_each_compatible(fdt, "arm,pl011", |node| {
+        let Some((base, _)) = node.reg else {
+            return;
+        };
+        if probed {
+            return;
+        }
+        probed = true;
+        UART = console_uart(base);
+        if let Some(rate) = node.clock_frequency {
+            set_default_uart_clock(rate);
+        }
+    });
+}
+
+unsafe fn probe_gpio(node: &DeviceNode) -> Option<DeviceDriverDescriptor> {
+    let (base, _) = node.reg?;
+    set_gpio_base(base);
+    Some(DeviceDriverDescriptor::new(&GPIO_CONTROLLER, None))
+}
+
+// Takes default pins of the bus, as pinctrl nodes are not parsed
+unsafe fn probe_i2c(node: &DeviceNode) -> Option<DeviceDriverDescriptor> {
+    let (base, _) = node.reg?;
+    let index = I2C_CONTROLLERS
+        .iter()
+        .position(|controller| controller.base_address() == base)?;
+    let bus = &I2C_BUSES[index];
+    if let Some(rate) = node.property_u32("clock-frequency") {
+        bus.set_clock_rate(rate);
+    }
+    Some(DeviceDriverDescriptor::new(bus, Some(I2C_POST_INIT[index])).with_irq(vc_irq(node)))
+}
+
+unsafe fn probe_spi(node: &DeviceNode) -> Option<DeviceDriverDescriptor> {
+    let (base, _) = node.reg?;
+    let index = SPI_CONTROLLERS
+        .iter()
+        .position(|controller| controller.base_address() == base)?;
+    Some(
+        DeviceDriverDescriptor::new(&SPI_BUSES[index], Some(SPI_POST_INIT[index]))
+            .with_irq(vc_irq(node)),
+    )
+}
+
+unsafe fn probe_pwm(node: &DeviceNode) -> Option<DeviceDriverDescriptor> {
+    let (base, _) = node.reg?;
+    let pwm: &'static Pwm = if base == PwmController::Pwm0.base_address() {
+        &PWM0
+    } else if base == PwmController::Pwm1.base_address() {
+        &PWM1
+    } else {
+        return None;
+    };
+    Some(DeviceDriverDescriptor::new(pwm, None))
+}
+
+unsafe fn probe_aux_spi(node: &DeviceNode) -> Option<DeviceDriverDescriptor> {
+    let (base, _) = node.reg?;
+    let spi: &'static AuxSpi = if base == AuxSpiController::Spi1.base_address() {
+        &AUX_SPI1
+    } else if base == AuxSpiController::Spi2.base_address() {
+        &AUX_SPI2
+    } else {
+        return None;
+    };
+    Some(DeviceDriverDescriptor::new(spi, None))
 }
 
 pub trait InitDriverTrait {
@@ -58,30 +306,74 @@ where
     type M: Mutex;
     unsafe fn get_inner(&mut self) -> &Self::M;
 }
-struct DriverManager<T>(pub Option<&'static mut T>)
-where
-    T: MutexControll + 'static,
-    <<T as MutexControll>::M as Mutex>::Data: InitDriverTrait;
 
-impl<T> DriverManager<T>
-where
-    T: MutexControll + 'static,
-    <<T as MutexControll>::M as Mutex>::Data: InitDriverTrait,
-{
-    fn register_driver(&mut self, driver: &'static mut T) {
-        self.0 = Some(driver);
+pub fn clock_manager() -> &'static ClockManager {
+    unsafe { &CLOCK_MANAGER }
+}
+pub fn mailbox() -> &'static Mailbox {
+    unsafe { &MAILBOX }
+}
+pub fn pwm(controller: PwmController) -> &'static Pwm {
+    unsafe {
+        match controller {
+            PwmController::Pwm0 => &PWM0,
+            PwmController::Pwm1 => &PWM1,
+        }
     }
-
-    unsafe fn init_drivers(&mut self) {
-        if let Some(mutex) = &mut self.0 {
-            mutex.get_inner().lock(|inner| inner.init_driver())
-        };
+}
+pub fn aux_spi(controller: AuxSpiController) -> &'static AuxSpi {
+    unsafe {
+        match controller {
+            AuxSpiController::Spi1 => &AUX_SPI1,
+            AuxSpiController::Spi2 => &AUX_SPI2,
+        }
     }
 }
-
-pub fn uart_manager() -> &'static mut DriverManager<Uart> {
-    unsafe { &mut UART_MANAGER }
+pub fn i2c_bus(bus: u32) -> Option<&'static I2C> {
+    unsafe { I2C_REGISTRY.get(bus as usize).copied().flatten() }
+}
+pub fn register_i2c_bus(bus: &'static I2C) {
+    unsafe { I2C_REGISTRY[bus.controller().number() as usize] = Some(bus) }
+}
+// Brings up bus on given SDA/SCL pair and makes it available by number
+pub unsafe fn enable_i2c_bus(
+    controller: I2cController,
+    sda: u32,
+    scl: u32,
+) -> Result<&'static I2C, I2cPinError> {
+    let bus = &I2C_BUSES[I2C_CONTROLLERS
+        .iter()
+        .position(|c| *c == controller)
+        .unwrap()];
+    bus.route_pins(sda, scl)?;
+    bus.init_driver();
+    register_i2c_bus(bus);
+    // All BSC masters share one interruption, enabling it again is harmless
+    bcm2711_irq::enable_vc_irq(bcm2711_irq::I2C_VC_IRQ);
+    Ok(bus)
+}
+pub fn bsc_slave() -> &'static BscSlave {
+    unsafe { &BSC_SLAVE }
+}
+fn spi_index(controller: SpiController) -> usize {
+    SPI_CONTROLLERS
+        .iter()
+        .position(|c| *c == controller)
+        .unwrap()
+}
+pub fn spi_bus(controller: SpiController) -> Option<&'static Spi> {
+    unsafe { SPI_REGISTRY[spi_index(controller)] }
+}
+pub fn register_spi_bus(bus: &'static Spi) {
+    unsafe { SPI_REGISTRY[spi_index(bus.controller())] = Some(bus) }
 }
-pub fn i2c_manager() -> &'static mut DriverManager<I2C> {
-    unsafe { &mut I2C_MANAGER }
+// Brings up bus on its fixed pins and makes it available to interrupt
+// handler, pins of SPI3-6 overlap other buses
+pub unsafe fn enable_spi_bus(controller: SpiController) -> Result<&'static Spi, GPIOError> {
+    let bus = &SPI_BUSES[spi_index(controller)];
+    bus.init_driver()?;
+    register_spi_bus(bus);
+    // All SPI controllers share one interruption, enabling it again is harmless
+    bcm2711_irq::enable_vc_irq(bcm2711_irq::SPI_VC_IRQ);
+    Ok(bus)
 }
@@ -0,0 +1,456 @@
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::{
+    bsp::bus::{SpiBus, SpiError, SpiMode},
+    bsp::clock::{rate_of, ClockId},
+    bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    driver::{interface::DeviceDriver, DriverError},
+    registers,
+    synchronization::{interface::Mutex, NullLock},
+};
+
+use super::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use super::{InitDriverTrait, MutexControll};
+
+// AUX block gates mini UART, SPI1 and SPI2 with a single register,
+// so every change has to keep bits of the other peripherals
+const AUX_ENABLES: *mut u32 = 0xfe21_5004 as *mut u32;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AuxPeripheral {
+    MiniUart = 1 << 0,
+    Spi1 = 1 << 1,
+    Spi2 = 1 << 2,
+}
+
+// Registers of peripheral are accessible only while it is enabled
+pub unsafe fn aux_enable(peripheral: AuxPeripheral) {
+    let enables = read_volatile(AUX_ENABLES);
+    write_volatile(AUX_ENABLES, enables | peripheral as u32);
+}
+
+pub unsafe fn aux_disable(peripheral: AuxPeripheral) {
+    let enables = read_volatile(AUX_ENABLES);
+    write_volatile(AUX_ENABLES, enables & !(peripheral as u32));
+}
+
+pub fn aux_is_enabled(peripheral: AuxPeripheral) -> bool {
+    unsafe { read_volatile(AUX_ENABLES) & peripheral as u32 != 0 }
+}
+
+//_____________________________________________________________
+//
+//  SPI1 / SPI2
+//_________________________________________
+//
+registers!(
+    (
+        REGISTER_NAME(CNTL0),
+        OFFSET(0x00),
+        PERM(Permission::ReadWrite)
+    ), // Control 0
+    (
+        REGISTER_NAME(CNTL1),
+        OFFSET(0x04),
+        PERM(Permission::ReadWrite)
+    ), // Control 1
+    (
+        REGISTER_NAME(STAT),
+        OFFSET(0x08),
+        PERM(Permission::ReadOnly)
+    ), // Status
+    (
+        REGISTER_NAME(PEEK),
+        OFFSET(0x0c),
+        PERM(Permission::ReadOnly)
+    ), // RX FIFO top entry, not popped
+    (REGISTER_NAME(IO), OFFSET(0x20), PERM(Permission::ReadWrite)), // Chip select released after entry
+    (
+        REGISTER_NAME(TXHOLD),
+        OFFSET(0x30),
+        PERM(Permission::ReadWrite)
+    )  // Chip select kept after entry
+);
+impl RegisterInterface for Registers {}
+type RegisterMapped = MIMODerefWrapper<Registers>;
+
+// Control 0 register
+const CNTL0_MSB_OUT: u32 = 1 << 6;
+const CNTL0_INVERT_CLK: u32 = 1 << 7;
+const CNTL0_OUT_RISING: u32 = 1 << 8;
+const CNTL0_CLEAR_FIFO: u32 = 1 << 9;
+const CNTL0_IN_RISING: u32 = 1 << 10;
+const CNTL0_ENABLE: u32 = 1 << 11;
+const CNTL0_VAR_WIDTH: u32 = 1 << 14;
+const CNTL0_CS_SHIFT: u32 = 17;
+const CNTL0_CS_MASK: u32 = 0b111;
+const CNTL0_SPEED_SHIFT: u32 = 20;
+// Control 1 register
+const CNTL1_MSB_IN: u32 = 1 << 1;
+// Status register
+const STAT_BUSY: u32 = 1 << 6;
+const STAT_RX_EMPTY: u32 = 1 << 7;
+const STAT_TX_FULL: u32 = 1 << 10;
+
+// Every FIFO entry carries up to 24 bits, width goes to bits 24-28
+const BYTES_PER_ENTRY: usize = 3;
+const WIDTH_SHIFT: u32 = 24;
+// Entries pushed but not read back yet, FIFO holds 4
+const MAX_PENDING: usize = 4;
+const MAX_SPEED: u32 = 0xfff;
+const POLL_LIMIT: u32 = 1_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AuxSpiController {
+    Spi1,
+    Spi2,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AuxChipSelect {
+    Cs0 = 0,
+    Cs1 = 1,
+    Cs2 = 2,
+}
+
+impl AuxSpiController {
+    pub const fn base_address(&self) -> usize {
+        match self {
+            AuxSpiController::Spi1 => 0x0_FE21_5080,
+            AuxSpiController::Spi2 => 0x0_FE21_50C0,
+        }
+    }
+    fn peripheral(&self) -> AuxPeripheral {
+        match self {
+            AuxSpiController::Spi1 => AuxPeripheral::Spi1,
+            AuxSpiController::Spi2 => AuxPeripheral::Spi2,
+        }
+    }
+    fn owner(&self) -> &'static str {
+        match self {
+            AuxSpiController::Spi1 => "spi1",
+            AuxSpiController::Spi2 => "spi2",
+        }
+    }
+    // SCLK, MOSI, MISO and CE0-CE2, all on Alt4
+    fn pins(&self) -> [u32; 6] {
+        match self {
+            AuxSpiController::Spi1 => [21, 20, 19, 18, 17, 16],
+            AuxSpiController::Spi2 => [42, 41, 40, 43, 44, 45],
+        }
+    }
+}
+
+pub struct AuxSpiInner {
+    registers: RegisterMapped,
+    controller: AuxSpiController,
+    mode: SpiMode,
+    clock_rate: u32,
+    chip_select: AuxChipSelect,
+    speed: u32,
+    pins: [Option<GPIODriver>; 6],
+    chars_written: usize,
+    chars_read: usize,
+}
+
+impl AuxSpiInner {
+    const fn new(controller: AuxSpiController, mode: SpiMode, clock_rate: u32) -> Self {
+        Self {
+            registers: unsafe { RegisterMapped::new(controller.base_address()) },
+            controller,
+            mode,
+            clock_rate,
+            chip_select: AuxChipSelect::Cs0,
+            speed: MAX_SPEED,
+            pins: [None, None, None, None, None, None],
+            chars_written: 0,
+            chars_read: 0,
+        }
+    }
+
+    // Old pins go back to registry before new ones are claimed
+    fn claim_pins(&mut self) -> Result<(), GPIOError> {
+        let owner = self.controller.owner();
+        self.pins = [None, None, None, None, None, None];
+        for (slot, pin) in self.pins.iter_mut().zip(self.controller.pins()) {
+            let driver = GPIODriver::claim(pin, GPIOFunction::Alt4, PullResistor::None, owner)?;
+            *slot = Some(driver);
+        }
+        Ok(())
+    }
+
+    // SCLK is core clock / (2 * (speed + 1)), returns rate actually generated
+    fn set_clock_rate(&mut self, rate: u32) -> u32 {
+        let core_rate = rate_of(ClockId::Core);
+        let speed = core_rate.div_ceil(2 * rate.max(1)).saturating_sub(1);
+        self.speed = speed.min(MAX_SPEED);
+        self.clock_rate = core_rate / (2 * (self.speed + 1));
+        self.clock_rate
+    }
+
+    fn control(&self) -> u32 {
+        let mut control = CNTL0_ENABLE | CNTL0_VAR_WIDTH | CNTL0_MSB_OUT;
+        if self.mode.cpol() {
+            control |= CNTL0_INVERT_CLK
+        }
+        // Edges are given on the real clock, so with idle high clock
+        // leading edge is the falling one
+        if self.mode.cpol() != self.mode.cpha() {
+            control |= CNTL0_OUT_RISING
+        } else {
+            control |= CNTL0_IN_RISING
+        }
+        // Selected line is the one with its bit cleared
+        let chip_selects = CNTL0_CS_MASK & !(1 << self.chip_select as u32);
+        control | chip_selects << CNTL0_CS_SHIFT | self.speed << CNTL0_SPEED_SHIFT
+    }
+
+    unsafe fn configure(&self) {
+        let control = self.control();
+        self.registers
+            .write_to_reg(Registers::CNTL0, control | CNTL0_CLEAR_FIFO)
+            .unwrap();
+        self.registers
+            .write_to_reg(Registers::CNTL0, control)
+            .unwrap();
+        self.registers
+            .write_to_reg(Registers::CNTL1, CNTL1_MSB_IN)
+            .unwrap();
+    }
+
+    unsafe fn status(&self) -> u32 {
+        self.registers.read_reg::<u32>(Registers::STAT).unwrap()
+    }
+
+    // Full duplex transfer, bytes are packed three per FIFO entry.
+    // Byte of index i is produced by tx and byte clocked in is given to rx.
+    // Chip select is released after last entry only with release set
+    unsafe fn exchange(
+        &mut self,
+        length: usize,
+        tx: impl Fn(usize) -> u8,
+        mut rx: impl FnMut(usize, u8),
+        release: bool,
+    ) -> Result<(), SpiError> {
+        let (mut written, mut read, mut polls) = (0, 0, 0);
+        while read < length {
+            let pending = (written - read).div_ceil(BYTES_PER_ENTRY);
+            if written < length && pending < MAX_PENDING && self.status() & STAT_TX_FULL == 0 {
+                let count = (length - written).min(BYTES_PER_ENTRY);
+                let mut entry = ((count * 8) as u32) << WIDTH_SHIFT;
+                for i in 0..count {
+                    entry |= (tx(written + i) as u32) << (8 * (BYTES_PER_ENTRY - 1 - i));
+                }
+                written += count;
+                // Last entry releases chip select once it is shifted out
+                let register = if written == length && release {
+                    Registers::IO
+                } else {
+                    Registers::TXHOLD
+                };
+                self.registers.write_to_reg(register, entry).unwrap();
+                polls = 0;
+            }
+            if self.status() & STAT_RX_EMPTY == 0 {
+                let entry = self.registers.read_reg::<u32>(Registers::IO).unwrap();
+                // Received bits are right aligned
+                let count = (length - read).min(BYTES_PER_ENTRY);
+                for i in 0..count {
+                    rx(read + i, (entry >> (8 * (count - 1 - i))) as u8);
+                }
+                read += count;
+                polls = 0;
+            }
+            polls += 1;
+            if polls == POLL_LIMIT {
+                self.configure();
+                return Err(SpiError::Timeout);
+            }
+        }
+        polls = 0;
+        while self.status() & STAT_BUSY != 0 {
+            polls += 1;
+            if polls == POLL_LIMIT {
+                return Err(SpiError::Timeout);
+            }
+        }
+        self.chars_written += length;
+        self.chars_read += length;
+        Ok(())
+    }
+}
+
+impl InitDriverTrait for AuxSpiInner {
+    unsafe fn init_driver(&mut self) {
+        aux_enable(self.controller.peripheral());
+        self.set_clock_rate(self.clock_rate);
+        self.configure();
+    }
+    unsafe fn clear_driver(&mut self) {
+        self.registers.write_to_reg(Registers::CNTL0, 0u32).unwrap();
+        aux_disable(self.controller.peripheral());
+        self.pins = [None, None, None, None, None, None];
+    }
+}
+
+pub struct AuxSpi {
+    pub inner: NullLock<AuxSpiInner>,
+}
+
+// Transfers made within AuxSpi::transaction
+pub struct AuxSpiTransaction<'a> {
+    inner: &'a mut AuxSpiInner,
+    release: bool,
+}
+
+impl AuxSpiTransaction<'_> {
+    // Controller releases chip select only together with an entry, so
+    // the last transfer of transaction has to be marked with it
+    pub fn release_after_next(&mut self) {
+        self.release = true
+    }
+    // Same as AuxSpi::transfer_split
+    pub fn transfer_split(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError> {
+        let length = read.len().max(write.len());
+        let release = core::mem::take(&mut self.release);
+        unsafe {
+            self.inner.exchange(
+                length,
+                |n| write.get(n).copied().unwrap_or(0),
+                |n, byte| {
+                    if let Some(slot) = read.get_mut(n) {
+                        *slot = byte
+                    }
+                },
+                release,
+            )
+        }
+    }
+    pub fn transfer(&mut self, data: &mut [u8]) -> Result<(), SpiError> {
+        let buffer = data.as_mut_ptr();
+        let release = core::mem::take(&mut self.release);
+        // Byte is always sent before the one received in its place is stored
+        unsafe {
+            self.inner.exchange(
+                data.len(),
+                |n| *buffer.add(n),
+                |n, byte| *buffer.add(n) = byte,
+                release,
+            )
+        }
+    }
+}
+
+impl AuxSpi {
+    pub const fn new(controller: AuxSpiController, mode: SpiMode, clock_rate: u32) -> Self {
+        Self {
+            inner: NullLock::new(AuxSpiInner::new(controller, mode, clock_rate)),
+        }
+    }
+    pub unsafe fn init_driver(&self) -> Result<(), GPIOError> {
+        self.inner.lock(|i| {
+            i.claim_pins()?;
+            i.init_driver();
+            Ok(())
+        })
+    }
+    pub unsafe fn clear_driver(&self) {
+        self.inner.lock(|i| i.clear_driver())
+    }
+    // Returns rate actually generated
+    pub fn set_clock_rate(&self, rate: u32) -> u32 {
+        self.inner.lock(|i| {
+            let rate = i.set_clock_rate(rate);
+            unsafe { i.configure() };
+            rate
+        })
+    }
+    pub fn clock_rate(&self) -> u32 {
+        self.inner.lock(|i| i.clock_rate)
+    }
+    pub fn set_mode(&self, mode: SpiMode) {
+        self.inner.lock(|i| {
+            i.mode = mode;
+            unsafe { i.configure() }
+        })
+    }
+    // Chip select lines are always active low on AUX SPI
+    pub fn select(&self, chip_select: AuxChipSelect) {
+        self.inner.lock(|i| {
+            i.chip_select = chip_select;
+            unsafe { i.configure() }
+        })
+    }
+    // Full duplex with separate buffers, bytes missing in write are sent
+    // as zeros and bytes past end of read are dropped
+    pub fn transfer_split(&self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError> {
+        let length = read.len().max(write.len());
+        self.inner.lock(|i| unsafe {
+            i.exchange(
+                length,
+                |n| write.get(n).copied().unwrap_or(0),
+                |n, byte| {
+                    if let Some(slot) = read.get_mut(n) {
+                        *slot = byte
+                    }
+                },
+                true,
+            )
+        })
+    }
+    // Chip select stays asserted between transfers made by f, see
+    // AuxSpiTransaction::release_after_next
+    pub fn transaction(
+        &self,
+        f: impl FnOnce(&mut AuxSpiTransaction) -> Result<(), SpiError>,
+    ) -> Result<(), SpiError> {
+        self.inner.lock(|i| {
+            f(&mut AuxSpiTransaction {
+                inner: i,
+                release: false,
+            })
+        })
+    }
+}
+
+impl DeviceDriver for AuxSpi {
+    fn name(&self) -> &'static str {
+        self.inner.lock(|i| i.controller.owner())
+    }
+    fn compatible(&self) -> &'static str {
+        "brcm,bcm2835-aux-spi"
+    }
+    unsafe fn init(&self) -> Result<(), DriverError> {
+        Ok(self.init_driver()?)
+    }
+}
+
+impl MutexControll for AuxSpi {
+    type M = NullLock<AuxSpiInner>;
+    unsafe fn get_inner(&mut self) -> &Self::M {
+        &self.inner
+    }
+}
+
+impl SpiBus for AuxSpi {
+    fn transfer(&self, data: &mut [u8]) -> Result<(), SpiError> {
+        let buffer = data.as_mut_ptr();
+        // Byte is always sent before the one received in its place is stored
+        self.inner.lock(|i| unsafe {
+            i.exchange(
+                data.len(),
+                |n| *buffer.add(n),
+                |n, byte| *buffer.add(n) = byte,
+                true,
+            )
+        })
+    }
+    fn write(&self, data: &[u8]) -> Result<(), SpiError> {
+        self.inner
+            .lock(|i| unsafe { i.exchange(data.len(), |n| data[n], |_, _| {}, true) })
+    }
+    fn read(&self, buffer: &mut [u8]) -> Result<(), SpiError> {
+        self.inner
+            .lock(|i| unsafe { i.exchange(buffer.len(), |_| 0, |n, byte| buffer[n] = byte, true) })
+    }
+}
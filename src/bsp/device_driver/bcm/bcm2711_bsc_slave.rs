@@ -0,0 +1,382 @@
+use crate::{
+    bsp::bus::SpiMode,
+    bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    registers,
+    synchronization::{interface::Mutex, NullLock},
+};
+
+use super::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use super::bcm2711_irq;
+use super::{InitDriverTrait, MutexControll};
+
+registers!(
+    (REGISTER_NAME(DR), OFFSET(0x00), PERM(Permission::ReadWrite)), // Data and flags
+    (
+        REGISTER_NAME(RSR),
+        OFFSET(0x04),
+        PERM(Permission::ReadWrite)
+    ), // Operation status and error clear
+    (
+        REGISTER_NAME(SLV),
+        OFFSET(0x08),
+        PERM(Permission::ReadWrite)
+    ), // I2C slave address
+    (REGISTER_NAME(CR), OFFSET(0x0c), PERM(Permission::ReadWrite)), // Control
+    (REGISTER_NAME(FR), OFFSET(0x10), PERM(Permission::ReadOnly)),  // Flags
+    (
+        REGISTER_NAME(IFLS),
+        OFFSET(0x14),
+        PERM(Permission::ReadWrite)
+    ), // Interrupt FIFO level select
+    (
+        REGISTER_NAME(IMSC),
+        OFFSET(0x18),
+        PERM(Permission::ReadWrite)
+    ), // Interrupt mask set/clear
+    (REGISTER_NAME(MIS), OFFSET(0x20), PERM(Permission::ReadOnly)), // Masked interrupt status
+    (
+        REGISTER_NAME(ICR),
+        OFFSET(0x24),
+        PERM(Permission::WriteOnly)
+    )  // Interrupt clear
+);
+impl RegisterInterface for Registers {}
+type RegisterMapped = MIMODerefWrapper<Registers>;
+
+const BSC_SLAVE_BASE: usize = 0x0_FE21_4000;
+pub const BSC_SLAVE_VC_IRQ: u32 = 43;
+// CE, MISO, SDA/MOSI and SCL/SCLK
+const BSC_SLAVE_PINS: [u32; 4] = [8, 9, 10, 11];
+
+// Control register
+const CR_EN: u32 = 1 << 0;
+const CR_SPI: u32 = 1 << 1;
+const CR_I2C: u32 = 1 << 2;
+const CR_CPHA: u32 = 1 << 3;
+const CR_CPOL: u32 = 1 << 4;
+const CR_BRK: u32 = 1 << 7;
+const CR_TXE: u32 = 1 << 8;
+const CR_RXE: u32 = 1 << 9;
+// Flag register
+const FR_TXBUSY: u32 = 1 << 0;
+const FR_RXFE: u32 = 1 << 1;
+const FR_TXFF: u32 = 1 << 2;
+const FR_RXBUSY: u32 = 1 << 5;
+const FR_TXFLEVEL_SHIFT: u32 = 6;
+const FR_TXFLEVEL_MASK: u32 = 0x1f;
+// Interrupt mask, RX and TX FIFO level
+const IMSC_RXIM: u32 = 1 << 0;
+const IMSC_TXIM: u32 = 1 << 1;
+const ICR_ALL: u32 = 0xf;
+
+// Bytes taken from on_read ahead of master reading them
+const TX_PREFETCH: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SlaveMode {
+    I2c { address: u8 },
+    Spi { mode: SpiMode },
+}
+
+// Called from interrupt handler. Controller sends bytes from its FIFO,
+// so on_read is called before master actually clocks the byte out
+pub trait SlaveHandler {
+    // Master wrote a byte. True drops bytes taken from on_read but not
+    // sent yet, e.g. when register pointer moved, and they are read again
+    fn on_write(&self, byte: u8) -> bool;
+    // Next byte for master to read
+    fn on_read(&self) -> u8;
+    // Master finished transfer, bytes taken from on_read but not sent
+    // are dropped and their count is given here. Usually seen by poll
+    fn on_stop(&self, unsent: usize);
+}
+
+pub struct BscSlaveInner {
+    registers: RegisterMapped,
+    mode: SlaveMode,
+    handler: Option<&'static dyn SlaveHandler>,
+    pins: Option<[GPIODriver; 4]>,
+    // Transfer was seen in progress, stop is reported once it ends
+    active: bool,
+    chars_written: usize,
+    chars_read: usize,
+}
+
+impl BscSlaveInner {
+    const fn new(mode: SlaveMode) -> Self {
+        Self {
+            registers: unsafe { RegisterMapped::new(BSC_SLAVE_BASE) },
+            mode,
+            handler: None,
+            pins: None,
+            active: false,
+            chars_written: 0,
+            chars_read: 0,
+        }
+    }
+
+    fn claim_pins(&mut self) -> Result<(), GPIOError> {
+        self.pins = None;
+        let [ce, miso, sda, scl] = BSC_SLAVE_PINS;
+        let claim = |pin| GPIODriver::claim(pin, GPIOFunction::Alt3, PullResistor::None, "bscsl");
+        self.pins = Some([claim(ce)?, claim(miso)?, claim(sda)?, claim(scl)?]);
+        Ok(())
+    }
+
+    fn control(&self) -> u32 {
+        let mode = match self.mode {
+            SlaveMode::I2c { .. } => CR_I2C,
+            SlaveMode::Spi { mode } => {
+                let mut bits = CR_SPI;
+                if mode.cpha() {
+                    bits |= CR_CPHA
+                }
+                if mode.cpol() {
+                    bits |= CR_CPOL
+                }
+                bits
+            }
+        };
+        CR_EN | CR_TXE | CR_RXE | mode
+    }
+
+    unsafe fn flags(&self) -> u32 {
+        self.registers.read_reg::<u32>(Registers::FR).unwrap()
+    }
+
+    // Drops unsent bytes, they were fetched for transfer that ended
+    unsafe fn flush_tx(&self) {
+        let control = self.control();
+        self.registers
+            .write_to_reg(Registers::CR, control | CR_BRK)
+            .unwrap();
+        self.registers.write_to_reg(Registers::CR, control).unwrap();
+    }
+
+    // Returns number of dropped bytes
+    unsafe fn drop_tx(&mut self) -> usize {
+        let unsent = ((self.flags() >> FR_TXFLEVEL_SHIFT) & FR_TXFLEVEL_MASK) as usize;
+        self.flush_tx();
+        self.chars_written -= unsent.min(self.chars_written);
+        unsent
+    }
+
+    unsafe fn fill_tx(&mut self, handler: &dyn SlaveHandler) {
+        let mut queued = 0;
+        while queued < TX_PREFETCH && self.flags() & FR_TXFF == 0 {
+            self.registers
+                .write_to_reg(Registers::DR, handler.on_read() as u32)
+                .unwrap();
+            queued += 1;
+            self.chars_written += 1;
+        }
+    }
+
+    // Returns true when master ended transfer
+    unsafe fn service(&mut self) -> bool {
+        let Some(handler) = self.handler else {
+            return false;
+        };
+        let mut stale = false;
+        while self.flags() & FR_RXFE == 0 {
+            let data = self.registers.read_reg::<u32>(Registers::DR).unwrap();
+            stale |= handler.on_write(data as u8);
+            self.chars_read += 1;
+            self.active = true;
+        }
+        // Break clears RX FIFO too, so it is done once RX is drained.
+        // Repeated start read then gets bytes from the new pointer
+        if stale {
+            self.drop_tx();
+            self.fill_tx(handler);
+        }
+        let flags = self.flags();
+        if flags & (FR_TXBUSY | FR_RXBUSY) != 0 {
+            self.active = true;
+            if flags & FR_TXBUSY != 0 {
+                self.fill_tx(handler);
+            }
+            return false;
+        }
+        if !self.active {
+            return false;
+        }
+        self.active = false;
+        // Register pointer may have moved, so prefetched bytes are stale
+        let unsent = self.drop_tx();
+        handler.on_stop(unsent);
+        self.fill_tx(handler);
+        true
+    }
+}
+
+impl InitDriverTrait for BscSlaveInner {
+    unsafe fn init_driver(&mut self) {
+        self.registers.write_to_reg(Registers::CR, 0u32).unwrap();
+        if let SlaveMode::I2c { address } = self.mode {
+            self.registers
+                .write_to_reg(Registers::SLV, address as u32 & 0x7f)
+                .unwrap();
+        }
+        self.registers.write_to_reg(Registers::RSR, 0u32).unwrap();
+        self.registers
+            .write_to_reg(Registers::ICR, ICR_ALL)
+            .unwrap();
+        // Lowest levels, every byte is handled as soon as possible
+        self.registers.write_to_reg(Registers::IFLS, 0u32).unwrap();
+        self.flush_tx();
+        if let Some(handler) = self.handler {
+            self.fill_tx(handler)
+        }
+        self.registers
+            .write_to_reg(Registers::IMSC, IMSC_RXIM | IMSC_TXIM)
+            .unwrap();
+    }
+    unsafe fn clear_driver(&mut self) {
+        self.registers.write_to_reg(Registers::IMSC, 0u32).unwrap();
+        self.registers.write_to_reg(Registers::CR, 0u32).unwrap();
+        self.pins = None;
+        self.handler = None;
+        self.active = false;
+    }
+}
+
+pub struct BscSlave {
+    pub inner: NullLock<BscSlaveInner>,
+}
+
+impl BscSlave {
+    pub const fn new(mode: SlaveMode) -> Self {
+        Self {
+            inner: NullLock::new(BscSlaveInner::new(mode)),
+        }
+    }
+    // Pins are shared with SPI0, so it has to be released first
+    pub unsafe fn enable(
+        &self,
+        mode: SlaveMode,
+        handler: &'static dyn SlaveHandler,
+    ) -> Result<(), GPIOError> {
+        self.inner.lock(|i| {
+            i.claim_pins()?;
+            i.mode = mode;
+            i.handler = Some(handler);
+            i.init_driver();
+            Ok(())
+        })?;
+        bcm2711_irq::enable_vc_irq(BSC_SLAVE_VC_IRQ);
+        Ok(())
+    }
+    pub unsafe fn disable(&self) {
+        bcm2711_irq::disable_vc_irq(BSC_SLAVE_VC_IRQ);
+        self.inner.lock(|i| i.clear_driver())
+    }
+    // Controller has no stop interruption, so it has to be called
+    // regularly while slave is enabled. Transfer that no other one
+    // follows is seen to end, and on_stop called, only here
+    pub fn poll(&self) -> bool {
+        self.inner.lock(|i| unsafe { i.service() })
+    }
+    pub unsafe fn handle_interrupt(&self) {
+        self.inner.lock(|i| {
+            i.service();
+            i.registers.write_to_reg(Registers::ICR, ICR_ALL).unwrap();
+        })
+    }
+    pub fn chars_read(&self) -> usize {
+        self.inner.lock(|i| i.chars_read)
+    }
+    pub fn chars_written(&self) -> usize {
+        self.inner.lock(|i| i.chars_written)
+    }
+}
+
+impl MutexControll for BscSlave {
+    type M = NullLock<BscSlaveInner>;
+    unsafe fn get_inner(&mut self) -> &Self::M {
+        &self.inner
+    }
+}
+
+//_____________________________________________________________
+//
+//  REGISTER FILE
+//_________________________________________
+//
+// Makes slave look like usual register mapped device: first byte
+// of write sets register pointer, following bytes are written from it
+// and reads continue from it, pointer wraps at N
+pub struct RegisterFileInner<const N: usize> {
+    registers: [u8; N],
+    // Next register master writes or gets
+    pointer: usize,
+    // Next written byte is the register pointer
+    expect_pointer: bool,
+    // Reads done ahead from pointer, it moves once they are sent
+    prefetched: usize,
+    // Called after master wrote a register
+    on_change: Option<fn(usize, u8)>,
+}
+
+pub struct RegisterFile<const N: usize> {
+    inner: NullLock<RegisterFileInner<N>>,
+}
+
+impl<const N: usize> RegisterFile<N> {
+    pub const fn new(on_change: Option<fn(usize, u8)>) -> Self {
+        Self {
+            inner: NullLock::new(RegisterFileInner {
+                registers: [0; N],
+                pointer: 0,
+                expect_pointer: true,
+                prefetched: 0,
+                on_change,
+            }),
+        }
+    }
+    pub fn get(&self, register: usize) -> u8 {
+        self.inner.lock(|i| i.registers[register % N])
+    }
+    pub fn set(&self, register: usize, value: u8) {
+        self.inner.lock(|i| i.registers[register % N] = value)
+    }
+}
+
+impl<const N: usize> SlaveHandler for RegisterFile<N> {
+    // Both pointer and register writes move pointer, so prefetched
+    // bytes are always dropped
+    fn on_write(&self, byte: u8) -> bool {
+        let changed = self.inner.lock(|i| {
+            i.prefetched = 0;
+            if i.expect_pointer {
+                i.pointer = byte as usize % N;
+                i.expect_pointer = false;
+                return None;
+            }
+            let register = i.pointer;
+            i.registers[register] = byte;
+            i.pointer = (register + 1) % N;
+            i.on_change.map(|callback| (callback, register))
+        });
+        if let Some((callback, register)) = changed {
+            callback(register, byte)
+        }
+        true
+    }
+    fn on_read(&self) -> u8 {
+        self.inner.lock(|i| {
+            let value = i.registers[(i.pointer + i.prefetched) % N];
+            i.prefetched += 1;
+            value
+        })
+    }
+    fn on_stop(&self, unsent: usize) {
+        self.inner.lock(|i| {
+            // Next read continues right after the last byte master got
+            let sent = i.prefetched - unsent.min(i.prefetched);
+            i.pointer = (i.pointer + sent) % N;
+            i.prefetched = 0;
+            i.expect_pointer = true;
+        })
+    }
+}
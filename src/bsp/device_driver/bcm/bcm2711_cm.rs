@@ -0,0 +1,365 @@
+use core::fmt;
+
+use crate::{
+    bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    registers,
+    synchronization::{interface::Mutex, NullLock},
+};
+
+use super::{InitDriverTrait, MutexControll};
+
+registers!(
+    (
+        REGISTER_NAME(GP0CTL),
+        OFFSET(0x70),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(GP0DIV),
+        OFFSET(0x74),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(GP1CTL),
+        OFFSET(0x78),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(GP1DIV),
+        OFFSET(0x7c),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(GP2CTL),
+        OFFSET(0x80),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(GP2DIV),
+        OFFSET(0x84),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(PCMCTL),
+        OFFSET(0x98),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(PCMDIV),
+        OFFSET(0x9c),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(PWMCTL),
+        OFFSET(0xa0),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(PWMDIV),
+        OFFSET(0xa4),
+        PERM(Permission::ReadWrite)
+    )
+);
+impl RegisterInterface for Registers {}
+type RegisterMapped = MIMODerefWrapper<Registers>;
+
+// Every write has to carry password in top byte, otherwise it is ignored
+const PASSWORD: u32 = 0x5a << 24;
+const CTL_SRC_MASK: u32 = 0xf;
+const CTL_ENAB: u32 = 1 << 4;
+const CTL_BUSY: u32 = 1 << 7;
+const CTL_MASH_SHIFT: u32 = 9;
+const CTL_MASH_MASK: u32 = 0b11 << CTL_MASH_SHIFT;
+const DIV_INTEGER_SHIFT: u32 = 12;
+const DIV_INTEGER_MASK: u32 = 0xfff;
+const DIV_FRACTION_MASK: u32 = 0xfff;
+const POLL_LIMIT: u32 = 1_000_000;
+
+pub const OSCILLATOR_RATE: u32 = 54_000_000;
+pub const PLLD_PER_RATE: u32 = 750_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClockGenerator {
+    Gp0,
+    Gp1,
+    Gp2,
+    Pcm,
+    Pwm,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClockSource {
+    Ground = 0,
+    Oscillator = 1,
+    TestDebug0 = 2,
+    TestDebug1 = 3,
+    PllaPer = 4,
+    // Follows core clock, so changes with firmware frequency scaling
+    PllcPer = 5,
+    PlldPer = 6,
+    HdmiAux = 7,
+}
+
+impl ClockSource {
+    fn from_bits(bits: u32) -> ClockSource {
+        match bits {
+            1 => ClockSource::Oscillator,
+            2 => ClockSource::TestDebug0,
+            3 => ClockSource::TestDebug1,
+            4 => ClockSource::PllaPer,
+            5 => ClockSource::PllcPer,
+            6 => ClockSource::PlldPer,
+            7 => ClockSource::HdmiAux,
+            _ => ClockSource::Ground,
+        }
+    }
+    pub fn rate(&self) -> Option<u32> {
+        match self {
+            ClockSource::Oscillator => Some(OSCILLATOR_RATE),
+            ClockSource::PlldPer => Some(PLLD_PER_RATE),
+            ClockSource::Ground => Some(0),
+            _ => None,
+        }
+    }
+}
+
+// Noise shaping of fractional divisor, higher stage means lower jitter
+// average but needs bigger integer divisor
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mash {
+    Integer = 0,
+    Stage1 = 1,
+    Stage2 = 2,
+    Stage3 = 3,
+}
+
+impl Mash {
+    fn minimal_divisor(&self) -> u32 {
+        match self {
+            Mash::Integer => 1,
+            Mash::Stage1 => 2,
+            Mash::Stage2 => 3,
+            Mash::Stage3 => 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockError {
+    // Generator did not stop before reconfiguration
+    Busy,
+    UnreachableRate(u32),
+    UnknownSourceRate,
+}
+
+impl fmt::Display for ClockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClockError::Busy => write!(f, "clock generator stays busy"),
+            ClockError::UnreachableRate(rate) => write!(f, "clock rate {} Hz not reachable", rate),
+            ClockError::UnknownSourceRate => write!(f, "clock source rate unknown"),
+        }
+    }
+}
+
+pub struct ClockManagerInner {
+    registers: RegisterMapped,
+}
+
+impl ClockManagerInner {
+    const fn new(start_addr: usize) -> Self {
+        Self {
+            registers: unsafe { RegisterMapped::new(start_addr) },
+        }
+    }
+
+    fn match_control_reg(generator: ClockGenerator) -> Register {
+        match generator {
+            ClockGenerator::Gp0 => Registers::GP0CTL,
+            ClockGenerator::Gp1 => Registers::GP1CTL,
+            ClockGenerator::Gp2 => Registers::GP2CTL,
+            ClockGenerator::Pcm => Registers::PCMCTL,
+            ClockGenerator::Pwm => Registers::PWMCTL,
+        }
+    }
+
+    fn match_divisor_reg(generator: ClockGenerator) -> Register {
+        match generator {
+            ClockGenerator::Gp0 => Registers::GP0DIV,
+            ClockGenerator::Gp1 => Registers::GP1DIV,
+            ClockGenerator::Gp2 => Registers::GP2DIV,
+            ClockGenerator::Pcm => Registers::PCMDIV,
+            ClockGenerator::Pwm => Registers::PWMDIV,
+        }
+    }
+
+    unsafe fn control(&self, generator: ClockGenerator) -> u32 {
+        self.registers
+            .read_reg::<u32>(Self::match_control_reg(generator))
+            .unwrap()
+    }
+
+    unsafe fn wait_idle(&self, generator: ClockGenerator) -> Result<(), ClockError> {
+        for _ in 0..POLL_LIMIT {
+            if self.control(generator) & CTL_BUSY == 0 {
+                return Ok(());
+            }
+        }
+        Err(ClockError::Busy)
+    }
+
+    // Clears ENAB and waits for BUSY to drop, generator finishes
+    // its current cycle first so output doesn't glitch
+    unsafe fn disable(&self, generator: ClockGenerator) -> Result<(), ClockError> {
+        let state = self.control(generator) & (CTL_SRC_MASK | CTL_MASH_MASK);
+        self.registers
+            .write_to_reg(Self::match_control_reg(generator), PASSWORD | state)
+            .unwrap();
+        self.wait_idle(generator)
+    }
+
+    unsafe fn enable(&self, generator: ClockGenerator) {
+        let state = self.control(generator) & (CTL_SRC_MASK | CTL_MASH_MASK);
+        self.registers
+            .write_to_reg(
+                Self::match_control_reg(generator),
+                PASSWORD | state | CTL_ENAB,
+            )
+            .unwrap();
+    }
+
+    // Source, divisor and MASH may only change while generator is idle
+    unsafe fn configure(
+        &self,
+        generator: ClockGenerator,
+        source: ClockSource,
+        divisor_integer: u32,
+        divisor_fraction: u32,
+        mash: Mash,
+    ) -> Result<(), ClockError> {
+        self.disable(generator)?;
+        self.registers
+            .write_to_reg(
+                Self::match_divisor_reg(generator),
+                PASSWORD
+                    | ((divisor_integer & DIV_INTEGER_MASK) << DIV_INTEGER_SHIFT)
+                    | (divisor_fraction & DIV_FRACTION_MASK),
+            )
+            .unwrap();
+        self.registers
+            .write_to_reg(
+                Self::match_control_reg(generator),
+                PASSWORD | ((mash as u32) << CTL_MASH_SHIFT) | source as u32,
+            )
+            .unwrap();
+        self.enable(generator);
+        Ok(())
+    }
+
+    unsafe fn set_rate(
+        &self,
+        generator: ClockGenerator,
+        source: ClockSource,
+        rate: u32,
+        mash: Mash,
+    ) -> Result<u32, ClockError> {
+        let source_rate = source.rate().ok_or(ClockError::UnknownSourceRate)?;
+        if rate == 0 {
+            return Err(ClockError::UnreachableRate(rate));
+        }
+        let divisor_integer = source_rate / rate;
+        let divisor_fraction = match mash {
+            Mash::Integer => 0,
+            _ => (((source_rate % rate) as u64 * 4096) / rate as u64) as u32,
+        };
+        if divisor_integer < mash.minimal_divisor() || divisor_integer > DIV_INTEGER_MASK {
+            return Err(ClockError::UnreachableRate(rate));
+        }
+        self.configure(generator, source, divisor_integer, divisor_fraction, mash)?;
+        Ok(self.rate(generator))
+    }
+
+    unsafe fn rate(&self, generator: ClockGenerator) -> u32 {
+        let control = self.control(generator);
+        if control & CTL_ENAB == 0 {
+            return 0;
+        }
+        let source_rate = match ClockSource::from_bits(control & CTL_SRC_MASK).rate() {
+            Some(rate) => rate,
+            None => return 0,
+        };
+        let divisor = self
+            .registers
+            .read_reg::<u32>(Self::match_divisor_reg(generator))
+            .unwrap();
+        let divisor_integer = (divisor >> DIV_INTEGER_SHIFT) & DIV_INTEGER_MASK;
+        let divisor_fraction = divisor & DIV_FRACTION_MASK;
+        let divisor = match control & CTL_MASH_MASK {
+            0 => (divisor_integer as u64) << 12,
+            _ => ((divisor_integer as u64) << 12) + divisor_fraction as u64,
+        };
+        if divisor == 0 {
+            return 0;
+        }
+        ((source_rate as u64) * 4096 / divisor) as u32
+    }
+}
+
+impl InitDriverTrait for ClockManagerInner {
+    // Generators keep whatever firmware configured
+    unsafe fn init_driver(&mut self) {}
+    unsafe fn clear_driver(&mut self) {}
+}
+
+pub struct ClockManager {
+    pub inner: NullLock<ClockManagerInner>,
+}
+
+impl ClockManager {
+    pub const fn new(start_addr: usize) -> Self {
+        Self {
+            inner: NullLock::new(ClockManagerInner::new(start_addr)),
+        }
+    }
+    // Returns rate actually generated, which may differ from requested one
+    pub fn set_rate(
+        &self,
+        generator: ClockGenerator,
+        source: ClockSource,
+        rate: u32,
+        mash: Mash,
+    ) -> Result<u32, ClockError> {
+        self.inner
+            .lock(|i| unsafe { i.set_rate(generator, source, rate, mash) })
+    }
+    pub fn configure(
+        &self,
+        generator: ClockGenerator,
+        source: ClockSource,
+        divisor_integer: u32,
+        divisor_fraction: u32,
+        mash: Mash,
+    ) -> Result<(), ClockError> {
+        self.inner.lock(|i| unsafe {
+            i.configure(generator, source, divisor_integer, divisor_fraction, mash)
+        })
+    }
+    pub fn enable(&self, generator: ClockGenerator) {
+        self.inner.lock(|i| unsafe { i.enable(generator) })
+    }
+    pub fn disable(&self, generator: ClockGenerator) -> Result<(), ClockError> {
+        self.inner.lock(|i| unsafe { i.disable(generator) })
+    }
+    pub fn rate(&self, generator: ClockGenerator) -> u32 {
+        self.inner.lock(|i| unsafe { i.rate(generator) })
+    }
+}
+
+impl MutexControll for ClockManager {
+    type M = NullLock<ClockManagerInner>;
+    unsafe fn get_inner(&mut self) -> &Self::M {
+        &self.inner
+    }
+}
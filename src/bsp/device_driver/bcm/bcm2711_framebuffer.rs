@@ -0,0 +1,354 @@
+use core::fmt;
+use core::ptr::{copy, write_volatile};
+
+use crate::synchronization::{interface::Mutex, NullLock};
+
+use super::bcm2711_mailbox::{MailboxError, PropertyMessage};
+use super::mailbox;
+
+mod font;
+
+const TAG_GET_PHYSICAL_SIZE: u32 = 0x0004_0003;
+const TAG_ALLOCATE_BUFFER: u32 = 0x0004_0001;
+const TAG_GET_PITCH: u32 = 0x0004_0008;
+const TAG_SET_PHYSICAL_SIZE: u32 = 0x0004_8003;
+const TAG_SET_VIRTUAL_SIZE: u32 = 0x0004_8004;
+const TAG_SET_DEPTH: u32 = 0x0004_8005;
+const TAG_SET_PIXEL_ORDER: u32 = 0x0004_8006;
+const TAG_SET_VIRTUAL_OFFSET: u32 = 0x0004_8009;
+
+const DEPTH: u32 = 32;
+const PIXEL_ORDER_RGB: u32 = 1;
+const BUFFER_ALIGNMENT: u32 = 16;
+// Firmware returns VideoCore bus address of the buffer
+const BUS_ADDRESS_MASK: u32 = 0x3fff_ffff;
+const DEFAULT_WIDTH: u32 = 1024;
+const DEFAULT_HEIGHT: u32 = 768;
+
+// Every font row is drawn twice, giving classic 8x16 text cell
+const CELL_WIDTH: usize = font::GLYPH_WIDTH;
+const CELL_HEIGHT: usize = font::GLYPH_HEIGHT * 2;
+const TAB_WIDTH: usize = 8;
+const MAX_ESCAPE_PARAMS: usize = 4;
+
+// ANSI colors 0-7 and their bright variants, as 0xRRGGBB
+const PALETTE: [u32; 16] = [
+    0x000000, 0xaa0000, 0x00aa00, 0xaa5500, 0x0000aa, 0xaa00aa, 0x00aaaa, 0xaaaaaa,
+    0x555555, 0xff5555, 0x55ff55, 0xffff55, 0x5555ff, 0xff55ff, 0x55ffff, 0xffffff,
+];
+const DEFAULT_FOREGROUND: u32 = PALETTE[7];
+const DEFAULT_BACKGROUND: u32 = PALETTE[0];
+
+pub struct Framebuffer {
+    base: usize,
+    width: usize,
+    height: usize,
+    // Bytes per line, may be bigger than width * 4
+    pitch: usize,
+    rgb_order: bool,
+}
+
+impl Framebuffer {
+    // Zero sizes take resolution of connected display
+    pub fn allocate(width: u32, height: u32) -> Result<Self, MailboxError> {
+        let (width, height) = if width == 0 || height == 0 {
+            let [width, height] = mailbox().property::<2>(TAG_GET_PHYSICAL_SIZE, &[])?;
+            match (width, height) {
+                (0, _) | (_, 0) => (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+                size => size,
+            }
+        } else {
+            (width, height)
+        };
+        let mut message = PropertyMessage::new();
+        let physical = message.add_tag(TAG_SET_PHYSICAL_SIZE, &[width, height], 2)?;
+        message.add_tag(TAG_SET_VIRTUAL_SIZE, &[width, height], 2)?;
+        message.add_tag(TAG_SET_VIRTUAL_OFFSET, &[0, 0], 2)?;
+        let depth = message.add_tag(TAG_SET_DEPTH, &[DEPTH], 1)?;
+        let order = message.add_tag(TAG_SET_PIXEL_ORDER, &[PIXEL_ORDER_RGB], 1)?;
+        let buffer = message.add_tag(TAG_ALLOCATE_BUFFER, &[BUFFER_ALIGNMENT], 2)?;
+        let pitch = message.add_tag(TAG_GET_PITCH, &[], 1)?;
+        mailbox().call(&mut message)?;
+
+        let buffer = message.tag_response(buffer)?;
+        let physical = message.tag_response(physical)?;
+        if message.tag_response(depth)?[0] != DEPTH || buffer[0] == 0 {
+            return Err(MailboxError::TagNotHandled(TAG_ALLOCATE_BUFFER));
+        }
+        Ok(Self {
+            base: (buffer[0] & BUS_ADDRESS_MASK) as usize,
+            width: physical[0] as usize,
+            height: physical[1] as usize,
+            pitch: message.tag_response(pitch)?[0] as usize,
+            rgb_order: message.tag_response(order)?[0] == PIXEL_ORDER_RGB,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Color is given as 0xRRGGBB
+    fn encode(&self, color: u32) -> u32 {
+        if !self.rgb_order {
+            return color;
+        }
+        let (r, g, b) = ((color >> 16) & 0xff, (color >> 8) & 0xff, color & 0xff);
+        (b << 16) | (g << 8) | r
+    }
+
+    fn pixel_address(&self, x: usize, y: usize) -> *mut u32 {
+        (self.base + y * self.pitch + x * 4) as *mut u32
+    }
+
+    pub fn put_pixel(&self, x: usize, y: usize, color: u32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        unsafe { write_volatile(self.pixel_address(x, y), self.encode(color)) }
+    }
+
+    pub fn fill_rect(&self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        let color = self.encode(color);
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                unsafe { write_volatile(self.pixel_address(column, row), color) }
+            }
+        }
+    }
+
+    pub fn clear(&self, color: u32) {
+        self.fill_rect(0, 0, self.width, self.height, color)
+    }
+
+    // Moves whole picture up and fills freed lines with color
+    pub fn scroll_up(&self, lines: usize, color: u32) {
+        let lines = lines.min(self.height);
+        unsafe {
+            copy(
+                (self.base + lines * self.pitch) as *const u8,
+                self.base as *mut u8,
+                (self.height - lines) * self.pitch,
+            );
+        }
+        self.fill_rect(0, self.height - lines, self.width, lines, color)
+    }
+
+    fn draw_char(&self, x: usize, y: usize, c: char, foreground: u32, background: u32) {
+        let glyph = font::glyph(c);
+        for row in 0..CELL_HEIGHT {
+            let bits = glyph[row / 2];
+            for column in 0..CELL_WIDTH {
+                let color = if bits & (1 << column) != 0 {
+                    foreground
+                } else {
+                    background
+                };
+                self.put_pixel(x + column, y + row, color)
+            }
+        }
+    }
+}
+
+//_____________________________________________________________
+//
+//  TEXT CONSOLE
+//_________________________________________
+//
+enum EscapeState {
+    Normal,
+    Escape,
+    // Inside CSI sequence, collecting numeric parameters
+    Csi,
+}
+
+pub struct FramebufferConsoleInner {
+    framebuffer: Framebuffer,
+    columns: usize,
+    rows: usize,
+    column: usize,
+    row: usize,
+    foreground: u32,
+    background: u32,
+    bright: bool,
+    escape_state: EscapeState,
+    params: [u32; MAX_ESCAPE_PARAMS],
+    param_count: usize,
+    chars_written: usize,
+}
+
+impl FramebufferConsoleInner {
+    fn new(framebuffer: Framebuffer) -> Self {
+        Self {
+            columns: framebuffer.width() / CELL_WIDTH,
+            rows: framebuffer.height() / CELL_HEIGHT,
+            framebuffer,
+            column: 0,
+            row: 0,
+            foreground: DEFAULT_FOREGROUND,
+            background: DEFAULT_BACKGROUND,
+            bright: false,
+            escape_state: EscapeState::Normal,
+            params: [0; MAX_ESCAPE_PARAMS],
+            param_count: 0,
+            chars_written: 0,
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.column = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+        self.framebuffer.scroll_up(CELL_HEIGHT, self.background);
+    }
+
+    fn put_char(&mut self, c: char) {
+        match c {
+            '\n' => self.new_line(),
+            '\r' => self.column = 0,
+            '\x08' => self.column = self.column.saturating_sub(1),
+            '\t' => {
+                self.column = (self.column / TAB_WIDTH + 1) * TAB_WIDTH;
+                if self.column >= self.columns {
+                    self.new_line()
+                }
+            }
+            '\x1b' => self.escape_state = EscapeState::Escape,
+            _ => {
+                if self.column >= self.columns {
+                    self.new_line()
+                }
+                self.framebuffer.draw_char(
+                    self.column * CELL_WIDTH,
+                    self.row * CELL_HEIGHT,
+                    c,
+                    self.foreground,
+                    self.background,
+                );
+                self.column += 1;
+            }
+        }
+    }
+
+    fn write_char(&mut self, c: char) {
+        match self.escape_state {
+            EscapeState::Normal => self.put_char(c),
+            EscapeState::Escape => {
+                if c == '[' {
+                    self.params = [0; MAX_ESCAPE_PARAMS];
+                    self.param_count = 0;
+                    self.escape_state = EscapeState::Csi;
+                } else {
+                    self.escape_state = EscapeState::Normal;
+                }
+            }
+            EscapeState::Csi => self.handle_csi(c),
+        }
+        self.chars_written += 1;
+    }
+
+    fn handle_csi(&mut self, c: char) {
+        match c {
+            '0'..='9' => {
+                let index = self.param_count.min(MAX_ESCAPE_PARAMS - 1);
+                self.params[index] = self.params[index] * 10 + c as u32 - '0' as u32;
+                if self.param_count == 0 {
+                    self.param_count = 1;
+                }
+            }
+            ';' => {
+                if self.param_count == 0 {
+                    self.param_count = 1;
+                }
+                self.param_count = (self.param_count + 1).min(MAX_ESCAPE_PARAMS);
+            }
+            'm' => {
+                self.set_graphic_rendition();
+                self.escape_state = EscapeState::Normal;
+            }
+            // Only clearing of whole screen is supported
+            'J' => {
+                self.framebuffer.clear(self.background);
+                self.column = 0;
+                self.row = 0;
+                self.escape_state = EscapeState::Normal;
+            }
+            'H' => {
+                let row = self.params[0].max(1) as usize - 1;
+                let column = self.params[1].max(1) as usize - 1;
+                self.row = row.min(self.rows - 1);
+                self.column = column.min(self.columns - 1);
+                self.escape_state = EscapeState::Normal;
+            }
+            _ => self.escape_state = EscapeState::Normal,
+        }
+    }
+
+    fn set_graphic_rendition(&mut self) {
+        // ESC[m is the same as ESC[0m
+        let count = self.param_count.max(1);
+        for i in 0..count {
+            match self.params[i] {
+                0 => {
+                    self.foreground = DEFAULT_FOREGROUND;
+                    self.background = DEFAULT_BACKGROUND;
+                    self.bright = false;
+                }
+                1 => self.bright = true,
+                22 => self.bright = false,
+                code @ 30..=37 => {
+                    let bright = if self.bright { 8 } else { 0 };
+                    self.foreground = PALETTE[(code - 30) as usize + bright]
+                }
+                39 => self.foreground = DEFAULT_FOREGROUND,
+                code @ 40..=47 => self.background = PALETTE[(code - 40) as usize],
+                49 => self.background = DEFAULT_BACKGROUND,
+                code @ 90..=97 => self.foreground = PALETTE[(code - 90) as usize + 8],
+                code @ 100..=107 => self.background = PALETTE[(code - 100) as usize + 8],
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Write for FramebufferConsoleInner {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.write_char(c)
+        }
+        Ok(())
+    }
+}
+
+pub struct FramebufferConsole {
+    inner: NullLock<FramebufferConsoleInner>,
+}
+
+impl FramebufferConsole {
+    pub fn new(framebuffer: Framebuffer) -> Self {
+        framebuffer.clear(DEFAULT_BACKGROUND);
+        Self {
+            inner: NullLock::new(FramebufferConsoleInner::new(framebuffer)),
+        }
+    }
+}
+
+impl crate::console::interface::Write for FramebufferConsole {
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
+    }
+}
+// Output only, there is no keyboard behind it
+impl crate::console::interface::Read for FramebufferConsole {}
+impl crate::console::interface::Statistics for FramebufferConsole {
+    fn chars_written(&self) -> usize {
+        self.inner.lock(|inner| inner.chars_written)
+    }
+}
+impl crate::console::interface::All for FramebufferConsole {}
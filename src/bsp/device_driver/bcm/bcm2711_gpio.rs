@@ -1,11 +1,24 @@
-use core::ops::{Drop, RangeBounds};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Drop;
 
 use crate::bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface};
 use crate::registers;
 use crate::synchronization::interface::Mutex;
 use crate::synchronization::NullLock;
 
+use super::bcm2711_irq;
 use super::InitDriverTrait;
+use crate::driver::{interface::DeviceDriver, DriverError};
+
+pub mod bank;
+pub mod debounce;
+
+// Device tree may move it before any pin is claimed
+static mut GPIO_BASE: usize = 0x0_FE20_0000;
+const GPIO_COUNT: usize = 58;
+// VideoCore gpio_int[0..2], one per pin bank
+const GPIO_BANK_IRQS: [(u32, u32); 3] = [(0, 49), (28, 50), (46, 51)];
 
 registers!(
     (
@@ -161,7 +174,7 @@ registers!(
 );
 
 #[repr(u8)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum GPIOFunction {
     Input = 0,
     Output = 1,
@@ -172,7 +185,30 @@ pub enum GPIOFunction {
     Alt4 = 0b011,
     Alt5 = 0b010,
 }
-enum GPIOLevel {
+// Alternative functions only, for pins that are routed to a peripheral
+#[derive(Clone, Copy, PartialEq)]
+pub enum AltFunction {
+    Alt0,
+    Alt1,
+    Alt2,
+    Alt3,
+    Alt4,
+    Alt5,
+}
+impl From<AltFunction> for GPIOFunction {
+    fn from(function: AltFunction) -> Self {
+        match function {
+            AltFunction::Alt0 => GPIOFunction::Alt0,
+            AltFunction::Alt1 => GPIOFunction::Alt1,
+            AltFunction::Alt2 => GPIOFunction::Alt2,
+            AltFunction::Alt3 => GPIOFunction::Alt3,
+            AltFunction::Alt4 => GPIOFunction::Alt4,
+            AltFunction::Alt5 => GPIOFunction::Alt5,
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq)]
+pub enum GPIOLevel {
     High,
     Low,
 }
@@ -180,6 +216,32 @@ enum EventState {
     NoEvent,
     EventOccured,
 }
+#[derive(Clone, Copy, PartialEq)]
+pub enum GPIOEvent {
+    RisingEdge,
+    FallingEdge,
+    High,
+    Low,
+    // Asynchronous variants aren't sampled by system clock,
+    // so they catch pulses shorter than a clock period
+    AsyncRisingEdge,
+    AsyncFallingEdge,
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GPIOError {
+    NotSupported(u32),
+    AlreadyClaimed { pin: u32, owner: &'static str },
+}
+impl fmt::Display for GPIOError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GPIOError::NotSupported(pin) => write!(f, "GPIO {} is not supported", pin),
+            GPIOError::AlreadyClaimed { pin, owner } => {
+                write!(f, "GPIO {} is already used by {}", pin, owner)
+            }
+        }
+    }
+}
 #[repr(u8)]
 #[derive(Clone, Copy)]
 pub enum PullResistor {
@@ -198,131 +260,116 @@ pub struct GPIOInner {
     pull_resistor: PullResistor,
 }
 impl GPIOInner {
-    const unsafe fn new(
+    unsafe fn new(
         pin: u32,
         function: GPIOFunction,
         pull_resistor: PullResistor,
     ) -> GPIOInner {
         Self {
             pin,
-            registers: RegisterMapped::new(0x0_FE20_000),
+            registers: RegisterMapped::new(gpio_base()),
             function,
             level: GPIOLevel::Low,
             pull_resistor,
         }
     }
 
+    // Bit of the pin in GPSET/GPCLR/GPLEV/GPEDS like registers
+    fn bank_mask(&self) -> u32 {
+        1 << (self.pin % 32)
+    }
+
     unsafe fn set_function_select(&self) {
-        let absolute_pin = match self.pin {
-            _ if self.pin > 10 && self.pin < 20 => self.pin - 10,
-            _ if self.pin > 20 && self.pin < 30 => self.pin - 20,
-            _ if self.pin > 30 && self.pin < 40 => self.pin - 30,
-            _ if self.pin > 40 && self.pin < 50 => self.pin - 40,
-            _ if self.pin > 50 && self.pin < 60 => self.pin - 50,
-            _ => self.pin,
-        };
-        let offset = absolute_pin * 3; // Fix for numbers greater than 16
+        // Every GPFSEL register holds 10 pins, 3 bits each
+        let offset = (self.pin % 10) * 3;
         let state = self
             .registers
             .read_reg::<u32>(self.match_function_reg())
             .unwrap();
+        let cleared_state = state & !(0b111 << offset);
         self.registers.write_to_reg(
             self.match_function_reg(),
-            state | ((self.function as u32) << offset),
+            cleared_state | ((self.function as u32) << offset),
         );
     }
     unsafe fn set_output(&self) {
-        if self.pin < 32 {
-            self.registers
-                .write_to_reg(self.match_output_reg(), 1 << self.pin);
-        } else {
-            self.registers
-                .write_to_reg(self.match_output_reg(), 1 << (self.pin - 32));
-        }
+        self.registers
+            .write_to_reg(self.match_output_reg(), self.bank_mask())
+            .unwrap();
     }
     unsafe fn clear_output(&self) {
-        if self.pin < 32 {
-            self.registers
-                .write_to_reg(self.match_clear_reg(), 1 << self.pin);
-        } else {
-            self.registers
-                .write_to_reg(self.match_clear_reg(), 1 << (self.pin - 32));
-        }
+        self.registers
+            .write_to_reg(self.match_clear_reg(), self.bank_mask())
+            .unwrap();
     }
-    unsafe fn get_level(&mut self) {
+    unsafe fn get_level(&mut self) -> GPIOLevel {
         let state = self
             .registers
             .read_reg::<u32>(self.match_level_reg())
             .unwrap();
-        if self.pin < 32 && (state & (1 << self.pin) == 1 << self.pin) {
-            self.level = GPIOLevel::High;
-            return;
-        }
-        if self.pin < 32 {
-            self.level = GPIOLevel::Low;
-            return;
-        }
-        if self.pin > 32 && (state & (1 << self.pin) == 1 << (self.pin - 32)) {
-            self.level = GPIOLevel::High;
-            return;
-        }
-        if self.pin > 32 {
-            self.level = GPIOLevel::Low;
-            return;
-        }
-        panic!("No pin detected")
+        self.level = if state & self.bank_mask() == self.bank_mask() {
+            GPIOLevel::High
+        } else {
+            GPIOLevel::Low
+        };
+        self.level
     }
     unsafe fn check_if_event_occured(&self) -> EventState {
         let state = self
             .registers
             .read_reg::<u32>(self.match_event_detect_register())
             .unwrap();
-        if self.pin < 32 && (state & (1 << self.pin) == 1 << self.pin) {
+        if state & self.bank_mask() == self.bank_mask() {
             return EventState::EventOccured;
         }
-        if self.pin < 32 {
-            return EventState::NoEvent;
-        }
-        if self.pin > 32 && (state == 1 << (self.pin - 32)) {
-            return EventState::EventOccured;
-        }
-        if self.pin > 32 {
-            return EventState::NoEvent;
-        }
-        panic!("No pin detected")
+        EventState::NoEvent
     }
-    unsafe fn set_pull_resistor(&self) {
-        let absolute_pin = match self.pin {
-            _ if self.pin >= 16 && self.pin <= 31 => self.pin - 16,
-            _ if self.pin >= 32 && self.pin <= 47 => self.pin - 32,
-            _ if self.pin >= 47 && self.pin <= 57 => self.pin - 48,
-            _ => self.pin,
+    unsafe fn clear_event(&self) {
+        // GPEDS bits are cleared by writing 1
+        self.registers
+            .write_to_reg(self.match_event_detect_register(), self.bank_mask())
+            .unwrap();
+    }
+    unsafe fn set_event_detection(&self, event: GPIOEvent, enabled: bool) {
+        let register = self.match_event_enable_reg(event);
+        let state = self.registers.read_reg::<u32>(register).unwrap();
+        let new_state = if enabled {
+            state | self.bank_mask()
+        } else {
+            state & !self.bank_mask()
         };
-        let offset = absolute_pin * 2;
+        self.registers
+            .write_to_reg(self.match_event_enable_reg(event), new_state)
+            .unwrap();
+    }
+    unsafe fn set_pull_resistor(&self) {
+        // Every pull control register holds 16 pins, 2 bits each
+        let offset = (self.pin % 16) * 2;
         let state = self
             .registers
             .read_reg::<u32>(self.match_pull_resistor_reg())
             .unwrap();
+        let cleared_state = state & !(0b11 << offset);
         self.registers.write_to_reg(
             self.match_pull_resistor_reg(),
-            state | ((self.pull_resistor as u32) << offset),
+            cleared_state | ((self.pull_resistor as u32) << offset),
         );
     }
 
     fn match_function_reg(&self) -> Register {
         match self.pin {
-            _ if self.pin >= 0 || self.pin <= 9 => Registers::GPFSEL0,
-            _ if self.pin >= 10 || self.pin <= 19 => Registers::GPFSEL1,
-            _ if self.pin >= 20 || self.pin <= 29 => Registers::GPFSEL2,
-            _ if self.pin >= 30 || self.pin <= 39 => Registers::GPFSEL3,
-            _ if self.pin >= 40 || self.pin <= 49 => Registers::GPFSEL4,
-            _ if self.pin >= 50 || self.pin <= 57 => Registers::GPFSEL5,
+            0..=9 => Registers::GPFSEL0,
+            10..=19 => Registers::GPFSEL1,
+            20..=29 => Registers::GPFSEL2,
+            30..=39 => Registers::GPFSEL3,
+            40..=49 => Registers::GPFSEL4,
+            50..=57 => Registers::GPFSEL5,
             _ => panic!("Not supported GPIO or register"),
         }
     }
     fn match_output_reg(&self) -> Register {
-        let first_segment = 0..31;
-        let second_segment = 31..57;
+        let first_segment = 0..32;
+        let second_segment = 32..58;
         match self.pin {
             _ if first_segment.contains(&self.pin) => Registers::GPSET0,
             _ if second_segment.contains(&self.pin) => Registers::GPSET1,
@@ -344,8 +391,8 @@ impl GPIOInner {
     }
 
     fn match_clear_reg(&self) -> Register {
-        let first_segment = 0..31;
-        let second_segment = 31..57;
+        let first_segment = 0..32;
+        let second_segment = 32..58;
         match self.pin {
             _ if first_segment.contains(&self.pin) => Registers::GPCLR0,
             _ if second_segment.contains(&self.pin) => Registers::GPCLR1,
@@ -353,8 +400,8 @@ impl GPIOInner {
         }
     }
     fn match_level_reg(&self) -> Register {
-        let first_segment = 0..31;
-        let second_segment = 31..57;
+        let first_segment = 0..32;
+        let second_segment = 32..58;
         match self.pin {
             _ if first_segment.contains(&self.pin) => Registers::GPLEV0,
             _ if second_segment.contains(&self.pin) => Registers::GPLEV1,
@@ -362,19 +409,39 @@ impl GPIOInner {
         }
     }
     fn match_event_detect_register(&self) -> Register {
-        let first_segment = 0..31;
-        let second_segment = 31..57;
+        let first_segment = 0..32;
+        let second_segment = 32..58;
         match self.pin {
             _ if first_segment.contains(&self.pin) => Registers::GPEDS0,
             _ if second_segment.contains(&self.pin) => Registers::GPEDS1,
             _ => panic!("Not supported GPIO or register"),
         }
     }
+    fn match_event_enable_reg(&self, event: GPIOEvent) -> Register {
+        let first_bank = self.pin < 32;
+        match event {
+            GPIOEvent::RisingEdge if first_bank => Registers::GPREN0,
+            GPIOEvent::RisingEdge => Registers::GPREN1,
+            GPIOEvent::FallingEdge if first_bank => Registers::GPFEN0,
+            GPIOEvent::FallingEdge => Registers::GPFEN1,
+            GPIOEvent::High if first_bank => Registers::GPHEN0,
+            GPIOEvent::High => Registers::GPHEN1,
+            GPIOEvent::Low if first_bank => Registers::GPLEN0,
+            GPIOEvent::Low => Registers::GPLEN1,
+            GPIOEvent::AsyncRisingEdge if first_bank => Registers::GPAREN0,
+            GPIOEvent::AsyncRisingEdge => Registers::GPAREN1,
+            GPIOEvent::AsyncFallingEdge if first_bank => Registers::GPAFEN0,
+            GPIOEvent::AsyncFallingEdge => Registers::GPAFEN1,
+        }
+    }
 }
 
 impl InitDriverTrait for GPIOInner {
     unsafe fn init_driver(&mut self) {
-        self.set_output();
+        // Only outputs get a defined level, and it is low until set otherwise
+        if self.function == GPIOFunction::Output {
+            self.clear_output();
+        }
         self.set_function_select();
         self.set_pull_resistor();
         self.get_level();
@@ -389,7 +456,7 @@ pub struct GPIODriver {
     inner: NullLock<GPIOInner>,
 }
 impl GPIODriver {
-    pub const unsafe fn new(
+    unsafe fn new(
         pin: u32,
         function: GPIOFunction,
         pull_resistor: PullResistor,
@@ -401,12 +468,384 @@ impl GPIODriver {
             inner: NullLock::new(GPIOInner::new(pin, function, pull_resistor)),
         }
     }
-    pub unsafe fn init(&self) {
+    // Only way to get a driver, pin is given back when driver is dropped
+    pub fn claim(
+        pin: u32,
+        function: GPIOFunction,
+        pull_resistor: PullResistor,
+        owner: &'static str,
+    ) -> Result<GPIODriver, GPIOError> {
+        claim_pin(pin, owner)?;
+        let driver = unsafe { GPIODriver::new(pin, function, pull_resistor) };
+        unsafe { driver.init() };
+        Ok(driver)
+    }
+    unsafe fn init(&self) {
         self.inner.lock(|driver| driver.init_driver());
     }
+    pub fn pin(&self) -> u32 {
+        self.inner.lock(|driver| driver.pin)
+    }
+    pub fn function(&self) -> GPIOFunction {
+        self.inner.lock(|driver| driver.function)
+    }
+    pub fn set_function(&self, function: GPIOFunction) {
+        self.inner.lock(|driver| {
+            driver.function = function;
+            unsafe { driver.set_function_select() }
+        })
+    }
+    pub fn set_pull_resistor(&self, pull_resistor: PullResistor) {
+        self.inner.lock(|driver| {
+            driver.pull_resistor = pull_resistor;
+            unsafe { driver.set_pull_resistor() }
+        })
+    }
+    pub fn set_high(&self) {
+        self.inner.lock(|driver| unsafe { driver.set_output() })
+    }
+    pub fn set_low(&self) {
+        self.inner.lock(|driver| unsafe { driver.clear_output() })
+    }
+    pub fn level(&self) -> GPIOLevel {
+        self.inner.lock(|driver| unsafe { driver.get_level() })
+    }
+    pub fn is_high(&self) -> bool {
+        self.level() == GPIOLevel::High
+    }
+    pub fn is_low(&self) -> bool {
+        self.level() == GPIOLevel::Low
+    }
+    pub fn enable_event(&self, event: GPIOEvent) {
+        self.inner
+            .lock(|driver| unsafe { driver.set_event_detection(event, true) })
+    }
+    pub fn disable_event(&self, event: GPIOEvent) {
+        self.inner
+            .lock(|driver| unsafe { driver.set_event_detection(event, false) })
+    }
+    pub fn event_occured(&self) -> bool {
+        self.inner.lock(|driver| unsafe {
+            matches!(driver.check_if_event_occured(), EventState::EventOccured)
+        })
+    }
+    pub fn clear_event(&self) {
+        self.inner.lock(|driver| unsafe { driver.clear_event() })
+    }
 }
 impl Drop for GPIODriver {
+    // Pin keeps its configuration and level, only ownership is released
     fn drop(&mut self) {
-        unsafe { self.inner.lock(|driver| driver.clear_driver()) }
+        release_pin(self.pin())
+    }
+}
+
+//_____________________________________________________________
+//
+//  CONTROLLER
+//_________________________________________
+//
+fn gpio_base() -> usize {
+    unsafe { GPIO_BASE }
+}
+
+// Pins claimed earlier keep old base, so it is set only while none is claimed
+pub unsafe fn set_gpio_base(base: usize) -> bool {
+    let free = PIN_OWNERS.lock(|owners| owners.iter().all(|owner| owner.is_none()));
+    if free {
+        GPIO_BASE = base;
+    }
+    free
+}
+
+// Whole pin block, pins are set up one by one as they are claimed
+pub struct GpioController;
+
+impl DeviceDriver for GpioController {
+    fn name(&self) -> &'static str {
+        "gpio"
+    }
+    fn compatible(&self) -> &'static str {
+        "brcm,bcm2711-gpio"
+    }
+    unsafe fn init(&self) -> Result<(), DriverError> {
+        Ok(())
+    }
+}
+
+//_____________________________________________________________
+//
+//  PIN OWNERSHIP
+//_________________________________________
+//
+static PIN_OWNERS: NullLock<[Option<&'static str>; GPIO_COUNT]> =
+    NullLock::new([None; GPIO_COUNT]);
+
+fn claim_pin(pin: u32, owner: &'static str) -> Result<(), GPIOError> {
+    if pin as usize >= GPIO_COUNT {
+        return Err(GPIOError::NotSupported(pin));
+    }
+    PIN_OWNERS.lock(|owners| match owners[pin as usize] {
+        Some(current_owner) => Err(GPIOError::AlreadyClaimed {
+            pin,
+            owner: current_owner,
+        }),
+        None => {
+            owners[pin as usize] = Some(owner);
+            Ok(())
+        }
+    })
+}
+
+fn release_pin(pin: u32) {
+    PIN_OWNERS.lock(|owners| owners[pin as usize] = None)
+}
+
+pub fn pin_owner(pin: u32) -> Option<&'static str> {
+    PIN_OWNERS.lock(|owners| owners.get(pin as usize).copied().flatten())
+}
+
+pub fn for_each_claimed_pin(mut f: impl FnMut(u32, &'static str)) {
+    for pin in 0..GPIO_COUNT as u32 {
+        if let Some(owner) = pin_owner(pin) {
+            f(pin, owner)
+        }
+    }
+}
+
+//_____________________________________________________________
+//
+//  TYPED PIN API
+//_________________________________________
+//
+pub struct Input;
+pub struct Output;
+pub struct Alt;
+
+// Pin with its mode tracked in type, so output only operations
+// can't be called on input and the other way round
+pub struct GpioPin<MODE> {
+    driver: GPIODriver,
+    mode: PhantomData<MODE>,
+}
+
+impl<MODE> GpioPin<MODE> {
+    fn configure(
+        pin: u32,
+        function: GPIOFunction,
+        pull_resistor: PullResistor,
+        owner: &'static str,
+    ) -> Result<GpioPin<MODE>, GPIOError> {
+        Ok(GpioPin {
+            driver: GPIODriver::claim(pin, function, pull_resistor, owner)?,
+            mode: PhantomData,
+        })
+    }
+    fn into_mode<NEW>(self, function: GPIOFunction) -> GpioPin<NEW> {
+        self.driver.set_function(function);
+        GpioPin {
+            driver: self.driver,
+            mode: PhantomData,
+        }
+    }
+    pub fn pin(&self) -> u32 {
+        self.driver.pin()
+    }
+    pub fn into_input(self, pull_resistor: PullResistor) -> GpioPin<Input> {
+        self.driver.set_pull_resistor(pull_resistor);
+        self.into_mode(GPIOFunction::Input)
+    }
+    pub fn into_output(self, level: GPIOLevel) -> GpioPin<Output> {
+        // Latch level first so pin doesn't glitch while switching to output
+        match level {
+            GPIOLevel::High => self.driver.set_high(),
+            GPIOLevel::Low => self.driver.set_low(),
+        }
+        self.into_mode(GPIOFunction::Output)
+    }
+    pub fn into_alt(self, function: AltFunction) -> GpioPin<Alt> {
+        self.into_mode(function.into())
+    }
+}
+
+impl GpioPin<Input> {
+    pub fn input(
+        pin: u32,
+        pull_resistor: PullResistor,
+        owner: &'static str,
+    ) -> Result<GpioPin<Input>, GPIOError> {
+        Self::configure(pin, GPIOFunction::Input, pull_resistor, owner)
+    }
+    pub fn is_high(&self) -> bool {
+        self.driver.is_high()
+    }
+    pub fn is_low(&self) -> bool {
+        self.driver.is_low()
+    }
+    pub fn enable_event(&self, event: GPIOEvent) {
+        self.driver.enable_event(event)
+    }
+    pub fn disable_event(&self, event: GPIOEvent) {
+        self.driver.disable_event(event)
+    }
+    pub fn event_occured(&self) -> bool {
+        self.driver.event_occured()
+    }
+    pub fn clear_event(&self) {
+        self.driver.clear_event()
+    }
+}
+
+impl GpioPin<Output> {
+    pub fn output(
+        pin: u32,
+        level: GPIOLevel,
+        owner: &'static str,
+    ) -> Result<GpioPin<Output>, GPIOError> {
+        let pin = Self::configure(pin, GPIOFunction::Output, PullResistor::None, owner)?;
+        if level == GPIOLevel::High {
+            pin.set_high();
+        }
+        Ok(pin)
+    }
+    pub fn set_high(&self) {
+        self.driver.set_high()
+    }
+    pub fn set_low(&self) {
+        self.driver.set_low()
+    }
+    pub fn toggle(&self) {
+        if self.driver.is_high() {
+            self.driver.set_low()
+        } else {
+            self.driver.set_high()
+        }
+    }
+    // Output latch is reflected in GPLEV while pin is driven
+    pub fn is_set_high(&self) -> bool {
+        self.driver.is_high()
+    }
+}
+
+impl GpioPin<Alt> {
+    pub fn alt(
+        pin: u32,
+        function: AltFunction,
+        pull_resistor: PullResistor,
+        owner: &'static str,
+    ) -> Result<GpioPin<Alt>, GPIOError> {
+        Self::configure(pin, function.into(), pull_resistor, owner)
+    }
+    pub fn function(&self) -> GPIOFunction {
+        self.driver.function()
+    }
+}
+
+//_____________________________________________________________
+//
+//  EVENT INTERRUPTS
+//_________________________________________
+//
+static EVENT_HANDLERS: NullLock<[Option<fn(u32)>; GPIO_COUNT]> =
+    NullLock::new([None; GPIO_COUNT]);
+
+const GPIO_EVENTS: [GPIOEvent; 6] = [
+    GPIOEvent::RisingEdge,
+    GPIOEvent::FallingEdge,
+    GPIOEvent::High,
+    GPIOEvent::Low,
+    GPIOEvent::AsyncRisingEdge,
+    GPIOEvent::AsyncFallingEdge,
+];
+
+// Pins sharing interruption with the given one and the interruption
+fn event_bank(pin: u32) -> (core::ops::Range<usize>, u32) {
+    let index = GPIO_BANK_IRQS
+        .iter()
+        .rposition(|(first_pin, _)| pin >= *first_pin)
+        .unwrap();
+    let (first_pin, irq) = GPIO_BANK_IRQS[index];
+    let end = GPIO_BANK_IRQS
+        .get(index + 1)
+        .map_or(GPIO_COUNT, |(next_pin, _)| *next_pin as usize);
+    (first_pin as usize..end, irq)
+}
+
+// Handler gets pin number and is called from IRQ context,
+// event detect bit is already cleared when it runs.
+// Pin is either polled with event_occured or driven by handler, never
+// both. Once a bank has a handler, every pin of it with event detection
+// needs one, as pending event left uncleared keeps interruption asserted
+pub fn register_event_handler(pin: u32, handler: fn(u32)) {
+    if pin as usize >= GPIO_COUNT {
+        panic!("No supported pin")
+    }
+    EVENT_HANDLERS.lock(|handlers| handlers[pin as usize] = Some(handler));
+    let (_, irq) = event_bank(pin);
+    unsafe { bcm2711_irq::enable_vc_irq(irq) }
+}
+
+// Turns event detection of pin off and drops its pending event,
+// bank interruption is disabled with the last handler of the bank
+pub fn unregister_event_handler(pin: u32) {
+    if pin as usize >= GPIO_COUNT {
+        panic!("No supported pin")
+    }
+    let (bank, irq) = event_bank(pin);
+    let bank_unused = EVENT_HANDLERS.lock(|handlers| {
+        handlers[pin as usize] = None;
+        handlers[bank].iter().all(|handler| handler.is_none())
+    });
+    unsafe {
+        // Register access only, function and claim of pin stay as they are
+        let inner = GPIOInner::new(pin, GPIOFunction::Input, PullResistor::None);
+        for event in GPIO_EVENTS {
+            inner.set_event_detection(event, false);
+        }
+        inner.clear_event();
+        if bank_unused {
+            bcm2711_irq::disable_vc_irq(irq)
+        }
+    }
+}
+
+pub unsafe fn handle_event_interrupt() {
+    let registers = RegisterMapped::new(gpio_base());
+    let pending = [
+        (0, registers.read_reg::<u32>(Registers::GPEDS0).unwrap()),
+        (32, registers.read_reg::<u32>(Registers::GPEDS1).unwrap()),
+    ];
+    for (first_pin, state) in pending {
+        // Events of pins without handler are left for event_occured polling
+        let handlers = EVENT_HANDLERS.lock(|handlers| *handlers);
+        let mut handled = 0u32;
+        for bit in 0..32 {
+            let pin = first_pin + bit;
+            if pin as usize >= GPIO_COUNT {
+                break;
+            }
+            if state & (1 << bit) != 0 && handlers[pin as usize].is_some() {
+                handled |= 1 << bit;
+            }
+        }
+        if handled == 0 {
+            continue;
+        }
+        // Clear before calling handlers so level events can retrigger
+        let register = if first_pin == 0 {
+            Registers::GPEDS0
+        } else {
+            Registers::GPEDS1
+        };
+        registers.write_to_reg(register, handled).unwrap();
+        for bit in 0..32 {
+            if handled & (1 << bit) == 0 {
+                continue;
+            }
+            let pin = first_pin + bit;
+            if let Some(handler) = handlers[pin as usize] {
+                handler(pin)
+            }
+        }
     }
 }
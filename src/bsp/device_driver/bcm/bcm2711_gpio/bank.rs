@@ -0,0 +1,178 @@
+use core::time::Duration;
+
+use super::{
+    gpio_base, GPIODriver, GPIOError, GPIOFunction, PullResistor, RegisterInterface,
+    RegisterMapped, Registers,
+};
+use crate::time;
+
+// Group of pins driven and sampled together. Bit `i` of every value
+// corresponds to `pins[i]`, so pins don't have to be adjacent
+pub struct GpioBank<const N: usize> {
+    // Kept only to hold ownership of pins
+    drivers: [Option<GPIODriver>; N],
+    pins: [u32; N],
+    // Set when pins are consecutive and in one register, value is then just shifted
+    contiguous: Option<(usize, u32)>,
+    registers: RegisterMapped,
+}
+
+impl<const N: usize> GpioBank<N> {
+    pub fn new(
+        pins: [u32; N],
+        function: GPIOFunction,
+        pull_resistor: PullResistor,
+        owner: &'static str,
+    ) -> Result<Self, GPIOError> {
+        if N > 32 {
+            panic!("GPIO bank can hold at most 32 pins")
+        }
+        let mut drivers: [Option<GPIODriver>; N] = core::array::from_fn(|_| None);
+        // Pins claimed so far are released by drop if any claim fails
+        for (driver, pin) in drivers.iter_mut().zip(pins) {
+            *driver = Some(GPIODriver::claim(pin, function, pull_resistor, owner)?);
+        }
+        let first = pins.first().copied().unwrap_or(0);
+        let consecutive = pins
+            .iter()
+            .enumerate()
+            .all(|(i, pin)| *pin == first + i as u32 && pin / 32 == first / 32);
+        Ok(Self {
+            drivers,
+            pins,
+            contiguous: consecutive.then_some(((first / 32) as usize, first % 32)),
+            registers: unsafe { RegisterMapped::new(gpio_base()) },
+        })
+    }
+
+    pub fn pins(&self) -> &[u32; N] {
+        &self.pins
+    }
+
+    pub fn set_function(&self, function: GPIOFunction) {
+        for driver in self.drivers.iter().flatten() {
+            driver.set_function(function)
+        }
+    }
+
+    fn value_mask(&self) -> u32 {
+        if N == 32 {
+            u32::MAX
+        } else {
+            (1 << N) - 1
+        }
+    }
+
+    // Splits value into per register masks of pins to be set and cleared
+    fn split(&self, value: u32) -> ([u32; 2], [u32; 2]) {
+        let mut set = [0u32; 2];
+        let mut clear = [0u32; 2];
+        if let Some((register, shift)) = self.contiguous {
+            let value = value & self.value_mask();
+            set[register] = value << shift;
+            clear[register] = (!value & self.value_mask()) << shift;
+            return (set, clear);
+        }
+        for (i, pin) in self.pins.iter().enumerate() {
+            let bit = 1 << (pin % 32);
+            if value & (1 << i) != 0 {
+                set[(pin / 32) as usize] |= bit
+            } else {
+                clear[(pin / 32) as usize] |= bit
+            }
+        }
+        (set, clear)
+    }
+
+    // Pins going high are set in one access and pins going low in next one,
+    // so bus is never seen in mixed state for longer than one register write
+    pub fn write(&self, value: u32) {
+        let (set, clear) = self.split(value);
+        unsafe {
+            if set[0] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPSET0, set[0])
+                    .unwrap();
+            }
+            if clear[0] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPCLR0, clear[0])
+                    .unwrap();
+            }
+            if set[1] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPSET1, set[1])
+                    .unwrap();
+            }
+            if clear[1] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPCLR1, clear[1])
+                    .unwrap();
+            }
+        }
+    }
+
+    // Drives high only pins selected by mask, others are untouched
+    pub fn set_bits(&self, mask: u32) {
+        let (set, _) = self.split(mask);
+        unsafe {
+            if set[0] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPSET0, set[0])
+                    .unwrap();
+            }
+            if set[1] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPSET1, set[1])
+                    .unwrap();
+            }
+        }
+    }
+
+    // Drives low only pins selected by mask, others are untouched
+    pub fn clear_bits(&self, mask: u32) {
+        let (set, _) = self.split(mask);
+        unsafe {
+            if set[0] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPCLR0, set[0])
+                    .unwrap();
+            }
+            if set[1] != 0 {
+                self.registers
+                    .write_to_reg(Registers::GPCLR1, set[1])
+                    .unwrap();
+            }
+        }
+    }
+
+    pub fn read(&self) -> u32 {
+        let levels = unsafe {
+            [
+                self.registers.read_reg::<u32>(Registers::GPLEV0).unwrap(),
+                self.registers.read_reg::<u32>(Registers::GPLEV1).unwrap(),
+            ]
+        };
+        if let Some((register, shift)) = self.contiguous {
+            return (levels[register] >> shift) & self.value_mask();
+        }
+        let mut value = 0;
+        for (i, pin) in self.pins.iter().enumerate() {
+            if levels[(pin / 32) as usize] & (1 << (pin % 32)) != 0 {
+                value |= 1 << i
+            }
+        }
+        value
+    }
+
+    // Fills buffer with consecutive reads, as fast as possible
+    // when interval is zero
+    pub fn sample(&self, buffer: &mut [u32], interval: Duration) {
+        for value in buffer.iter_mut() {
+            *value = self.read();
+            if !interval.is_zero() {
+                time::spin_for(interval)
+            }
+        }
+    }
+}
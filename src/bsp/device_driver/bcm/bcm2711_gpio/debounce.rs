@@ -0,0 +1,106 @@
+use core::time::Duration;
+
+use super::{GPIOEvent, GpioPin, Input};
+use crate::time;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ButtonEvent {
+    Pressed,
+    Released,
+    // Reported once per press, while button is still held
+    LongPress,
+}
+
+// Input reported as changed only after its level stays the same
+// for whole stable time window
+pub struct DebouncedInput {
+    pin: GpioPin<Input>,
+    active_low: bool,
+    stable_time: Duration,
+    long_press_time: Option<Duration>,
+    pressed: bool,
+    last_raw: bool,
+    last_change: Duration,
+    pressed_at: Duration,
+    long_press_reported: bool,
+}
+
+impl DebouncedInput {
+    pub fn new(pin: GpioPin<Input>, active_low: bool, stable_time: Duration) -> Self {
+        let mut input = Self {
+            pin,
+            active_low,
+            stable_time,
+            long_press_time: None,
+            pressed: false,
+            last_raw: false,
+            last_change: time::uptime(),
+            pressed_at: Duration::ZERO,
+            long_press_reported: false,
+        };
+        // Button held during boot is not reported as press
+        input.last_raw = input.raw_pressed();
+        input.pressed = input.last_raw;
+        input
+    }
+    pub fn with_long_press(mut self, long_press_time: Duration) -> Self {
+        self.long_press_time = Some(long_press_time);
+        self
+    }
+    pub fn pin(&self) -> u32 {
+        self.pin.pin()
+    }
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+    // Lets interrupt path call on_edge for every bounce
+    pub fn enable_edge_events(&self) {
+        self.pin.enable_event(GPIOEvent::RisingEdge);
+        self.pin.enable_event(GPIOEvent::FallingEdge);
+    }
+    pub fn disable_edge_events(&self) {
+        self.pin.disable_event(GPIOEvent::RisingEdge);
+        self.pin.disable_event(GPIOEvent::FallingEdge);
+    }
+
+    fn raw_pressed(&self) -> bool {
+        self.pin.is_high() != self.active_low
+    }
+
+    // To be called from GPIO event handler, restarts stable time window.
+    // Events are still reported by poll
+    pub fn on_edge(&mut self) {
+        self.last_raw = self.raw_pressed();
+        self.last_change = time::uptime();
+    }
+
+    pub fn poll(&mut self) -> Option<ButtonEvent> {
+        let now = time::uptime();
+        let raw = self.raw_pressed();
+        if raw != self.last_raw {
+            self.last_raw = raw;
+            self.last_change = now;
+            return None;
+        }
+        if raw != self.pressed && now - self.last_change >= self.stable_time {
+            self.pressed = raw;
+            if raw {
+                self.pressed_at = now;
+                self.long_press_reported = false;
+                return Some(ButtonEvent::Pressed);
+            }
+            return Some(ButtonEvent::Released);
+        }
+        match self.long_press_time {
+            Some(long_press_time)
+                if self.pressed
+                    && !self.long_press_reported
+                    && now - self.pressed_at >= long_press_time =>
+            {
+                self.long_press_reported = true;
+                Some(ButtonEvent::LongPress)
+            }
+            _ => None,
+        }
+    }
+}
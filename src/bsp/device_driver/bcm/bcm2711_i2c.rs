@@ -1,12 +1,41 @@
 use crate::{
+    bsp::clock::{rate_of, ClockId},
     bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    driver::{interface::DeviceDriver, DriverError},
     registers,
     synchronization::{interface::Mutex, NullLock},
 };
 
+use super::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use super::bcm2711_irq::I2C_VC_IRQ;
 use super::{InitDriverTrait, MutexControll};
+use crate::bsp::bus::{I2cBus, SlaveAddress};
+use core::fmt;
 
-const CORE_CLK: u32 = 150_000_000;
+// Upper bound of status register polls without progress
+// before transfer is treated as hung
+const POLL_LIMIT: u32 = 1_000_000;
+// DLEN is 16 bits wide
+const MAX_TRANSFER_LENGTH: usize = 0xffff;
+// Write part of repeated start transfer has to be queued up front
+const FIFO_DEPTH: usize = 16;
+
+// Control register flags
+const C_READ: u32 = 1 << 0;
+const C_CLEAR: u32 = 0b11 << 4;
+const C_ST: u32 = 1 << 7;
+const C_INTD: u32 = 1 << 8;
+const C_INTT: u32 = 1 << 9;
+const C_INTR: u32 = 1 << 10;
+const C_I2CEN: u32 = 1 << 15;
+
+// Status register flags
+const S_TA: u32 = 1 << 0;
+const S_DONE: u32 = 1 << 1;
+const S_TXD: u32 = 1 << 4;
+const S_RXD: u32 = 1 << 5;
+const S_ERR: u32 = 1 << 8;
+const S_CLKT: u32 = 1 << 9;
 
 registers!(
     (REGISTER_NAME(C), OFFSET(0x00), PERM(Permission::ReadWrite)),
@@ -41,120 +70,491 @@ registers!(
 impl RegisterInterface for Registers {}
 type RegisterMapped = MIMODerefWrapper<Registers>;
 
-enum OperType {
-    Read,
-    Write,
+// BSC2 and BSC7 are wired to HDMI and are not usable from ARM side
+#[derive(Clone, Copy, PartialEq)]
+pub enum I2cController {
+    I2c0,
+    I2c1,
+    I2c3,
+    I2c4,
+    I2c5,
+    I2c6,
+}
+
+pub const I2C_CONTROLLERS: [I2cController; 6] = [
+    I2cController::I2c0,
+    I2cController::I2c1,
+    I2cController::I2c3,
+    I2cController::I2c4,
+    I2cController::I2c5,
+    I2cController::I2c6,
+];
+
+impl I2cController {
+    pub const fn base_address(&self) -> usize {
+        match self {
+            I2cController::I2c0 => 0x0_FE20_5000,
+            I2cController::I2c1 => 0x0_FE80_4000,
+            I2cController::I2c3 => 0x0_FE20_5600,
+            I2cController::I2c4 => 0x0_FE20_5800,
+            I2cController::I2c5 => 0x0_FE20_5A00,
+            I2cController::I2c6 => 0x0_FE20_5C00,
+        }
+    }
+    // Bus number as used by shell and device drivers
+    pub const fn number(&self) -> u32 {
+        match self {
+            I2cController::I2c0 => 0,
+            I2cController::I2c1 => 1,
+            I2cController::I2c3 => 3,
+            I2cController::I2c4 => 4,
+            I2cController::I2c5 => 5,
+            I2cController::I2c6 => 6,
+        }
+    }
+    pub fn from_number(number: u32) -> Option<Self> {
+        I2C_CONTROLLERS
+            .iter()
+            .find(|controller| controller.number() == number)
+            .copied()
+    }
+    fn owner(&self) -> &'static str {
+        match self {
+            I2cController::I2c0 => "i2c0",
+            I2cController::I2c1 => "i2c1",
+            I2cController::I2c3 => "i2c3",
+            I2cController::I2c4 => "i2c4",
+            I2cController::I2c5 => "i2c5",
+            I2cController::I2c6 => "i2c6",
+        }
+    }
+}
+
+// Valid SDA/SCL pairs of every controller
+const I2C_PINS: [(I2cController, u32, u32, GPIOFunction); 13] = [
+    (I2cController::I2c0, 0, 1, GPIOFunction::Alt0),
+    (I2cController::I2c0, 28, 29, GPIOFunction::Alt0),
+    (I2cController::I2c0, 44, 45, GPIOFunction::Alt1),
+    (I2cController::I2c1, 2, 3, GPIOFunction::Alt0),
+    (I2cController::I2c1, 44, 45, GPIOFunction::Alt2),
+    (I2cController::I2c3, 2, 3, GPIOFunction::Alt5),
+    (I2cController::I2c3, 4, 5, GPIOFunction::Alt5),
+    (I2cController::I2c4, 6, 7, GPIOFunction::Alt5),
+    (I2cController::I2c4, 8, 9, GPIOFunction::Alt5),
+    (I2cController::I2c5, 10, 11, GPIOFunction::Alt5),
+    (I2cController::I2c5, 12, 13, GPIOFunction::Alt5),
+    (I2cController::I2c6, 22, 23, GPIOFunction::Alt5),
+    (I2cController::I2c6, 0, 1, GPIOFunction::Alt5),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum I2cPinError {
+    // Pair can't be routed to this controller
+    InvalidPins { sda: u32, scl: u32 },
+    Gpio(GPIOError),
+}
+
+impl fmt::Display for I2cPinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            I2cPinError::InvalidPins { sda, scl } => {
+                write!(f, "GPIO {}/{} are not SDA/SCL of this bus", sda, scl)
+            }
+            I2cPinError::Gpio(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<GPIOError> for I2cPinError {
+    fn from(error: GPIOError) -> Self {
+        I2cPinError::Gpio(error)
+    }
+}
+
+// Given to callback once interrupt driven transfer ends
+pub struct I2cCompletion {
+    pub result: Result<(), I2cError>,
+    pub buffer: &'static mut [u8],
+    // Bytes moved through FIFO before transfer ended
+    pub transferred: usize,
+}
+
+pub type I2cCallback = fn(I2cCompletion);
+
+// State of running interrupt driven transfer
+struct AsyncTransfer {
+    buffer: &'static mut [u8],
+    position: usize,
+    read: bool,
+    callback: I2cCallback,
 }
 
 pub struct I2CInner {
     registers: RegisterMapped,
+    controller: I2cController,
+    pins: Option<[GPIODriver; 2]>,
+    transfer: Option<AsyncTransfer>,
     chars_written: usize,
     chars_read: usize,
     clock_rate: u32,
     timeout: u16,
-    data_length: usize,
 }
 
-enum TransferType {
-    Read,
-    Write,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum I2cError {
+    // Slave did not acknowledge address or data byte (S.ERR)
+    Nack,
+    // Slave held SCL low longer than CLKT.TOUT allows (S.CLKT)
+    ClockStretchTimeout,
+    // Another master drove SDA low while we released it
+    ArbitrationLost,
+    // Previous transfer is still active (S.TA) when new one starts
+    BusBusy,
+    // Address does not fit into 7 or 10 bits
+    InvalidAddress,
+    // Controller stopped making progress
+    Timeout,
+    // Transfer does not fit into DLEN, or write part of
+    // repeated start transfer does not fit into FIFO
+    TooLong,
+    // Sequence of operations controller cannot make without stop
+    Unsupported,
+}
+
+impl fmt::Display for I2cError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            I2cError::Nack => write!(f, "no acknowledge from slave"),
+            I2cError::ClockStretchTimeout => write!(f, "clock stretch timeout"),
+            I2cError::ArbitrationLost => write!(f, "arbitration lost"),
+            I2cError::BusBusy => write!(f, "bus busy"),
+            I2cError::InvalidAddress => write!(f, "invalid slave address"),
+            I2cError::Timeout => write!(f, "transfer timed out"),
+            I2cError::TooLong => write!(f, "transfer too long"),
+            I2cError::Unsupported => write!(f, "unsupported transfer sequence"),
+        }
+    }
 }
 impl I2CInner {
-    pub const fn new(start_addr: usize, clock_rate: u32, timeout: u16) -> Self {
+    pub const fn new(controller: I2cController, clock_rate: u32, timeout: u16) -> Self {
         Self {
-            registers: unsafe { RegisterMapped::new(start_addr) },
+            registers: unsafe { RegisterMapped::new(controller.base_address()) },
+            controller,
+            pins: None,
+            transfer: None,
             chars_read: 0,
             chars_written: 0,
             timeout,
             clock_rate,
-            data_length: 0,
         }
     }
-    unsafe fn start_transfer(&self) {
-        let state = self.registers.read_reg::<u32>(Registers::C).unwrap();
-        if let 0 = state & (1 << 15) {
-            panic!("I2C is not enabled to start new transaction")
+    fn route_pins(&mut self, sda: u32, scl: u32) -> Result<(), I2cPinError> {
+        let (_, _, _, function) = I2C_PINS
+            .iter()
+            .find(|(controller, pin_sda, pin_scl, _)| {
+                *controller == self.controller && *pin_sda == sda && *pin_scl == scl
+            })
+            .ok_or(I2cPinError::InvalidPins { sda, scl })?;
+        // Claiming own pins again would fail
+        if let Some([old_sda, old_scl]) = &self.pins {
+            if old_sda.pin() == sda && old_scl.pin() == scl {
+                return Ok(());
+            }
         }
-        self.registers
-            .write_to_reg::<u32>(Registers::C, state | (1 << 7))
-            .unwrap()
+        let owner = self.controller.owner();
+        // Old pins are released only once new ones are claimed, so failed
+        // claim leaves bus where it was
+        let pins = [
+            GPIODriver::claim(sda, *function, PullResistor::Up, owner)?,
+            GPIODriver::claim(scl, *function, PullResistor::Up, owner)?,
+        ];
+        self.pins = Some(pins);
+        Ok(())
     }
-    unsafe fn clear_fifo(&self) {
-        let state = self.registers.read_reg::<u32>(Registers::C).unwrap();
-        self.registers
-            .write_to_reg::<u32>(Registers::C, state | (0b11 << 4))
-            .unwrap();
-    }
-    unsafe fn read_fifo<const DATA_LENGTH: usize>(&self) -> [u8; DATA_LENGTH] {
-        let mut data: [u8; DATA_LENGTH] = [0; DATA_LENGTH];
-        for i in 0..self.data_length {
-            data[i as usize] = self.registers.read_reg::<u8>(Registers::FIFO).unwrap();
+    // First listed pair, for BSC1 the header pins 3/5
+    fn route_default_pins(&mut self) -> Result<(), I2cPinError> {
+        if self.pins.is_some() {
+            return Ok(());
         }
-        data
-    }
-    unsafe fn set_transfer_type(&self, transfer_type: TransferType) {
-        let state = self.registers.read_reg::<u32>(Registers::C).unwrap();
-        match transfer_type {
-            TransferType::Read => self
-                .registers
-                .write_to_reg::<u32>(Registers::C, state | 1)
-                .unwrap(),
-            TransferType::Write => self
-                .registers
-                .write_to_reg::<u32>(Registers::C, (state | 1) ^ 1)
-                .unwrap(),
+        match I2C_PINS
+            .iter()
+            .find(|(controller, ..)| *controller == self.controller)
+        {
+            Some((_, sda, scl, _)) => self.route_pins(*sda, *scl),
+            None => Ok(()),
         }
     }
+    unsafe fn status(&self) -> u32 {
+        self.registers.read_reg::<u32>(Registers::S).unwrap()
+    }
     unsafe fn set_timeout(&self) {
         self.registers
-            .write_to_reg(Registers::CLKT, self.timeout as u32);
+            .write_to_reg(Registers::CLKT, self.timeout as u32)
+            .unwrap();
     }
-    unsafe fn set_data_length(&self) {
+    unsafe fn clear_status(&self) {
+        // Flags are cleared by writing 1
         self.registers
-            .write_to_reg(Registers::DLEN, self.data_length)
+            .write_to_reg(Registers::S, S_DONE | S_ERR | S_CLKT)
+            .unwrap()
+    }
+    // Error flags are checked before DONE, as controller raises them together
+    fn check_errors(status: u32) -> Result<(), I2cError> {
+        if status & S_ERR == S_ERR {
+            return Err(I2cError::Nack);
+        }
+        if status & S_CLKT == S_CLKT {
+            return Err(I2cError::ClockStretchTimeout);
+        }
+        Ok(())
+    }
+    // Leaves controller ready for next transfer whatever the result is
+    unsafe fn finish(&self, result: Result<(), I2cError>) -> Result<(), I2cError> {
+        let mut polls = 0;
+        while self.status() & S_TA == S_TA && polls < POLL_LIMIT {
+            polls += 1;
+        }
+        self.registers
+            .write_to_reg(Registers::C, C_I2CEN | C_CLEAR)
             .unwrap();
+        self.clear_status();
+        result
     }
-
-    unsafe fn read_slave<const DATA_LENGTH: usize>(&mut self, slave_addr: u8) {
-        if let 1 = slave_addr & 1 << 7 {
-            panic!("I2C bus supports only 7 bits address")
+    // Address register takes 7 bit address or 10 bit header
+    unsafe fn start(
+        &self,
+        slave_addr: u8,
+        length: usize,
+        read: bool,
+        interrupts: u32,
+    ) -> Result<(), I2cError> {
+        if length > MAX_TRANSFER_LENGTH {
+            return Err(I2cError::TooLong);
+        }
+        if self.transfer.is_some() || self.status() & S_TA == S_TA {
+            return Err(I2cError::BusBusy);
         }
-        self.data_length = DATA_LENGTH;
-        self.set_data_length();
-        self.clear_fifo();
+        self.registers
+            .write_to_reg(Registers::C, C_I2CEN | C_CLEAR)
+            .unwrap();
+        self.clear_status();
         self.registers
             .write_to_reg(Registers::A, slave_addr as u32)
             .unwrap();
-        self.set_transfer_type(TransferType::Read);
-        self.start_transfer();
-        self.read_fifo::<DATA_LENGTH>();
+        self.registers
+            .write_to_reg(Registers::DLEN, length as u32)
+            .unwrap();
+        let direction = if read { C_READ } else { 0 };
+        self.registers
+            .write_to_reg(Registers::C, C_I2CEN | C_ST | direction | interrupts)
+            .unwrap();
+        Ok(())
     }
-    unsafe fn clear_status(&self) {
-        self.registers.write_to_reg(Registers::S, 1 << 1).unwrap();
-        self.registers.write_to_reg(Registers::S, 1 << 8).unwrap();
-        self.registers.write_to_reg(Registers::S, 1 << 9).unwrap()
+    // FIFO is refilled whenever it has room, so length is not limited by its depth.
+    // Prefix carries second address byte of 10 bit addressing
+    unsafe fn stream_write(&mut self, prefix: &[u8], data: &[u8]) -> Result<(), I2cError> {
+        let length = prefix.len() + data.len();
+        let byte = |i: usize| match prefix.get(i) {
+            Some(byte) => *byte,
+            None => data[i - prefix.len()],
+        };
+        let (mut written, mut polls) = (0, 0);
+        loop {
+            let status = self.status();
+            Self::check_errors(status)?;
+            while written < length && self.status() & S_TXD == S_TXD {
+                self.registers
+                    .write_to_reg(Registers::FIFO, byte(written) as u32)
+                    .unwrap();
+                written += 1;
+                polls = 0;
+            }
+            if status & S_DONE == S_DONE {
+                self.chars_written += written.saturating_sub(prefix.len());
+                return Ok(());
+            }
+            polls += 1;
+            if polls == POLL_LIMIT {
+                return Err(I2cError::Timeout);
+            }
+        }
     }
-
-    unsafe fn write_slave(&mut self, slave_addr: u8, data: &str) {
-        if let 128 = slave_addr & 1 << 7 {
-            panic!("I2C bus supports only 7 bits address")
+    unsafe fn stream_read(&mut self, buffer: &mut [u8]) -> Result<(), I2cError> {
+        let (mut read, mut polls) = (0, 0);
+        loop {
+            let status = self.status();
+            Self::check_errors(status)?;
+            while read < buffer.len() && self.status() & S_RXD == S_RXD {
+                buffer[read] = self.registers.read_reg::<u32>(Registers::FIFO).unwrap() as u8;
+                read += 1;
+                polls = 0;
+            }
+            // Last bytes may still wait in FIFO when DONE is raised
+            if status & S_DONE == S_DONE && read == buffer.len() {
+                self.chars_read += read;
+                return Ok(());
+            }
+            polls += 1;
+            if polls == POLL_LIMIT {
+                return Err(I2cError::Timeout);
+            }
+        }
+    }
+    unsafe fn write(&mut self, address: SlaveAddress, data: &[u8]) -> Result<(), I2cError> {
+        let (header, low) = address.header()?;
+        let prefix = low.as_slice();
+        self.start(header, prefix.len() + data.len(), false, 0)?;
+        let result = self.stream_write(prefix, data);
+        self.finish(result)
+    }
+    unsafe fn read(&mut self, address: SlaveAddress, buffer: &mut [u8]) -> Result<(), I2cError> {
+        let (header, low) = address.header()?;
+        // 10 bit read needs full address written first, then
+        // repeated start with header alone
+        if low.is_some() {
+            return self.write_then_read(address, &[], buffer);
+        }
+        self.start(header, buffer.len(), true, 0)?;
+        let result = self.stream_read(buffer);
+        self.finish(result)
+    }
+    // BSC can't be told to skip the stop, but read started while write
+    // is still active makes it send repeated start instead
+    unsafe fn write_then_read(
+        &mut self,
+        address: SlaveAddress,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        let (header, low) = address.header()?;
+        let prefix = low.as_slice();
+        if prefix.len() + data.len() > FIFO_DEPTH || buffer.len() > MAX_TRANSFER_LENGTH {
+            return Err(I2cError::TooLong);
+        }
+        if self.transfer.is_some() || self.status() & S_TA == S_TA {
+            return Err(I2cError::BusBusy);
         }
-        self.set_transfer_type(TransferType::Write);
         self.registers
-            .write_to_reg(Registers::A, slave_addr as u32)
+            .write_to_reg(Registers::C, C_I2CEN | C_CLEAR)
             .unwrap();
-        self.data_length = data.len();
-        self.set_data_length();
-        self.clear_fifo();
-        for c in data.chars() {
-            self.registers.write_to_reg(Registers::FIFO, c).unwrap()
-        }
         self.clear_status();
-        self.start_transfer();
+        self.registers
+            .write_to_reg(Registers::A, header as u32)
+            .unwrap();
+        self.registers
+            .write_to_reg(Registers::DLEN, (prefix.len() + data.len()) as u32)
+            .unwrap();
+        for byte in prefix.iter().chain(data) {
+            self.registers
+                .write_to_reg(Registers::FIFO, *byte as u32)
+                .unwrap();
+        }
+        self.registers
+            .write_to_reg(Registers::C, C_I2CEN | C_ST)
+            .unwrap();
+        // Read has to be queued once write is on the bus, but before it drains
+        let mut polls = 0;
+        loop {
+            let status = self.status();
+            if let Err(error) = Self::check_errors(status) {
+                return self.finish(Err(error));
+            }
+            if status & S_TA == S_TA {
+                break;
+            }
+            if status & S_DONE == S_DONE {
+                // Write finished before it was seen active, stop was already sent
+                return self.finish(Err(I2cError::Timeout));
+            }
+            polls += 1;
+            if polls == POLL_LIMIT {
+                return self.finish(Err(I2cError::Timeout));
+            }
+        }
+        self.registers
+            .write_to_reg(Registers::DLEN, buffer.len() as u32)
+            .unwrap();
+        self.registers
+            .write_to_reg(Registers::C, C_I2CEN | C_ST | C_READ)
+            .unwrap();
+        self.chars_written += data.len();
+        let result = self.stream_read(buffer);
+        self.finish(result)
+    }
+    unsafe fn start_async(
+        &mut self,
+        slave_addr: u8,
+        buffer: &'static mut [u8],
+        read: bool,
+        callback: I2cCallback,
+    ) -> Result<(), I2cError> {
+        // Reads are drained on RXR and DONE, writes refilled on TXW
+        let interrupts = if read { C_INTR | C_INTD } else { C_INTT | C_INTD };
+        self.start(slave_addr, buffer.len(), read, interrupts)?;
+        self.transfer = Some(AsyncTransfer {
+            buffer,
+            position: 0,
+            read,
+            callback,
+        });
+        Ok(())
+    }
+    // Moves bytes between FIFO and buffer, returns callback
+    // and its argument once transfer ends
+    unsafe fn handle_interrupt(&mut self) -> Option<(I2cCallback, I2cCompletion)> {
+        let registers = &self.registers;
+        let status = || registers.read_reg::<u32>(Registers::S).unwrap();
+        let transfer = self.transfer.as_mut()?;
+        let length = transfer.buffer.len();
+        let current = status();
+        let mut result = Self::check_errors(current);
+        if result.is_ok() && transfer.read {
+            while transfer.position < length && status() & S_RXD == S_RXD {
+                transfer.buffer[transfer.position] =
+                    registers.read_reg::<u32>(Registers::FIFO).unwrap() as u8;
+                transfer.position += 1;
+            }
+        } else if result.is_ok() {
+            while transfer.position < length && status() & S_TXD == S_TXD {
+                registers
+                    .write_to_reg(Registers::FIFO, transfer.buffer[transfer.position] as u32)
+                    .unwrap();
+                transfer.position += 1;
+            }
+            // TXW would keep firing with nothing left to send
+            if transfer.position == length {
+                let control = registers.read_reg::<u32>(Registers::C).unwrap();
+                registers
+                    .write_to_reg(Registers::C, control & !(C_INTT | C_ST))
+                    .unwrap();
+            }
+        }
+        if result.is_ok() && current & S_DONE != S_DONE {
+            return None;
+        }
+        if result.is_ok() && transfer.position != length {
+            result = Err(I2cError::Timeout);
+        }
+        let transfer = self.transfer.take()?;
+        if transfer.read {
+            self.chars_read += transfer.position;
+        } else {
+            self.chars_written += transfer.position;
+        }
+        // Also masks interruptions of this controller
+        let result = self.finish(result);
+        Some((
+            transfer.callback,
+            I2cCompletion {
+                result,
+                buffer: transfer.buffer,
+                transferred: transfer.position,
+            },
+        ))
     }
 
     unsafe fn set_clock_rate(&self) {
-        let divisor = CORE_CLK / self.clock_rate;
+        let divisor = rate_of(ClockId::Core) / self.clock_rate;
         self.registers
             .write_to_reg(Registers::DIV, divisor)
             .unwrap();
@@ -162,33 +562,177 @@ impl I2CInner {
 }
 impl InitDriverTrait for I2CInner {
     unsafe fn init_driver(&mut self) {
-        self.registers.write_to_reg(Registers::C, 1 << 15).unwrap();
+        self.registers
+            .write_to_reg(Registers::C, C_I2CEN | C_CLEAR)
+            .unwrap();
+        self.clear_status();
         self.set_clock_rate();
         self.set_timeout();
-        self.write_slave(0x7f, "I2C Init Done")
     }
-    unsafe fn clear_driver(&mut self) {}
+    unsafe fn clear_driver(&mut self) {
+        self.registers.write_to_reg(Registers::C, 0u32).unwrap();
+        self.transfer = None;
+        self.pins = None;
+    }
 }
 pub struct I2C {
     pub inner: NullLock<I2CInner>,
 }
 
 impl I2C {
-    pub const fn new(start_addr: usize, clock_rate: u32, timeout: u16) -> Self {
+    pub const fn new(controller: I2cController, clock_rate: u32, timeout: u16) -> Self {
         Self {
-            inner: NullLock::new(I2CInner::new(start_addr, clock_rate, timeout)),
+            inner: NullLock::new(I2CInner::new(controller, clock_rate, timeout)),
         }
     }
     pub unsafe fn init_driver(&self) {
         self.inner.lock(|i| i.init_driver())
     }
-    pub unsafe fn write(&self, slave_addr: u8, data: &str) {
-        self.inner.lock(|i| i.write_slave(slave_addr, data))
+    pub fn controller(&self) -> I2cController {
+        self.inner.lock(|i| i.controller)
+    }
+    // SCL frequency, divided down from core clock
+    pub fn set_clock_rate(&self, rate: u32) {
+        self.inner.lock(|i| {
+            i.clock_rate = rate;
+            unsafe { i.set_clock_rate() }
+        })
+    }
+    // Claims SDA and SCL with alternative function of this bus
+    pub fn route_pins(&self, sda: u32, scl: u32) -> Result<(), I2cPinError> {
+        self.inner.lock(|i| i.route_pins(sda, scl))
+    }
+    // Plain u8 is taken as 7 bit address
+    pub fn write(&self, address: impl Into<SlaveAddress>, data: &[u8]) -> Result<(), I2cError> {
+        let address = address.into();
+        self.inner.lock(|i| unsafe { i.write(address, data) })
+    }
+    pub fn read(
+        &self,
+        address: impl Into<SlaveAddress>,
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        let address = address.into();
+        self.inner.lock(|i| unsafe { i.read(address, buffer) })
+    }
+    // Stop condition is sent between write and read part
+    pub fn write_stop_read(
+        &self,
+        address: impl Into<SlaveAddress>,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        let address = address.into();
+        self.inner.lock(|i| unsafe {
+            i.write(address, data)?;
+            i.read(address, buffer)
+        })
+    }
+    // Repeated start between write and read part, as register reads
+    // of most sensors expect. Write part is limited to 16 bytes,
+    // 15 with 10 bit address
+    pub fn write_then_read(
+        &self,
+        address: impl Into<SlaveAddress>,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        let address = address.into();
+        self.inner
+            .lock(|i| unsafe { i.write_then_read(address, data, buffer) })
+    }
+    // Interrupt driven variants return right after transfer starts,
+    // callback gets the buffer back from interrupt handler.
+    // Only 7 bit addresses are supported here
+    pub fn write_async(
+        &self,
+        slave_addr: u8,
+        data: &'static mut [u8],
+        callback: I2cCallback,
+    ) -> Result<(), I2cError> {
+        self.inner
+            .lock(|i| unsafe { i.start_async(slave_addr, data, false, callback) })
+    }
+    pub fn read_async(
+        &self,
+        slave_addr: u8,
+        buffer: &'static mut [u8],
+        callback: I2cCallback,
+    ) -> Result<(), I2cError> {
+        self.inner
+            .lock(|i| unsafe { i.start_async(slave_addr, buffer, true, callback) })
+    }
+    pub fn is_busy(&self) -> bool {
+        self.inner.lock(|i| i.transfer.is_some())
+    }
+    pub unsafe fn handle_interrupt(&self) {
+        // Callback runs outside of lock, so it can start next transfer
+        if let Some((callback, completion)) = self.inner.lock(|i| i.handle_interrupt()) {
+            callback(completion)
+        }
+    }
+    // Checks if any slave acknowledges given address with single byte read
+    pub fn probe(&self, address: impl Into<SlaveAddress>) -> Result<(), I2cError> {
+        self.read(address, &mut [0u8; 1])
+    }
+    pub fn read_register(
+        &self,
+        address: impl Into<SlaveAddress>,
+        register: u8,
+    ) -> Result<u8, I2cError> {
+        let mut data = [0u8; 1];
+        self.write_then_read(address, &[register], &mut data)?;
+        Ok(data[0])
+    }
+    pub fn write_register(
+        &self,
+        address: impl Into<SlaveAddress>,
+        register: u8,
+        value: u8,
+    ) -> Result<(), I2cError> {
+        self.write(address, &[register, value])
+    }
+}
+// Bus keeps pins routed earlier, otherwise takes default ones
+impl DeviceDriver for I2C {
+    fn name(&self) -> &'static str {
+        self.controller().owner()
+    }
+    fn compatible(&self) -> &'static str {
+        "brcm,bcm2711-i2c"
+    }
+    unsafe fn init(&self) -> Result<(), DriverError> {
+        self.inner.lock(|i| {
+            i.route_default_pins()?;
+            i.init_driver();
+            Ok(())
+        })
+    }
+    fn irq_number(&self) -> Option<u32> {
+        Some(I2C_VC_IRQ)
     }
 }
+
 impl MutexControll for I2C {
     type M = NullLock<I2CInner>;
     unsafe fn get_inner(&mut self) -> &Self::M {
         &self.inner
     }
 }
+
+impl I2cBus for I2C {
+    fn write(&self, address: SlaveAddress, data: &[u8]) -> Result<(), I2cError> {
+        I2C::write(self, address, data)
+    }
+    fn read(&self, address: SlaveAddress, buffer: &mut [u8]) -> Result<(), I2cError> {
+        I2C::read(self, address, buffer)
+    }
+    fn write_read(
+        &self,
+        address: SlaveAddress,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        I2C::write_then_read(self, address, data, buffer)
+    }
+}
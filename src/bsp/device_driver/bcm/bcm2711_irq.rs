@@ -1,4 +1,7 @@
-use core::ptr::read_volatile;
+use core::ptr::{read_volatile, write_volatile};
+
+use super::bcm2711_gpio;
+use super::bcm2711_spi::SpiController;
 // ARM GIC-400 disctibutor offset starts with 0x1000
 // ARM GIC-400 Shered Peripheral Interrupt Status Register starts with 0xD04
 // PACTL_CS register at  0x7E20 4E00 -> 0xFE20_4E00
@@ -7,33 +10,73 @@ use core::ptr::read_volatile;
 //  PACTL_CS (at address 0x7E20 4E00) registers
 
 const PACTL_CS: *const u32 = 0xfe20_4e00 as *const u32;
-const AUX_IRQ: *const u32 = 0xfe21_500 as *const u32;
-const MIMOIRQ: [*const u32; 5] = [
-    0xff841D08 as *const u32,
-    0xff841D10 as *const u32,
-    0xff841D18 as *const u32,
-    0xff841D20 as *const u32,
-    0xff841D28 as *const u32,
-]; // All segments where IRQ occures
+const AUX_IRQ: *const u32 = 0xfe21_5000 as *const u32;
+// GIC-400 distributor and CPU interface
+const GICD_BASE: usize = 0xff84_1000;
+const GICC_BASE: usize = 0xff84_2000;
+const GICD_CTLR: *mut u32 = GICD_BASE as *mut u32;
+const GICD_ISENABLER: usize = GICD_BASE + 0x100;
+const GICD_ICENABLER: usize = GICD_BASE + 0x180;
+const GICD_ITARGETSR: usize = GICD_BASE + 0x800;
+const GICC_CTLR: *mut u32 = GICC_BASE as *mut u32;
+const GICC_PMR: *mut u32 = (GICC_BASE + 0x04) as *mut u32;
+const GICC_IAR: *const u32 = (GICC_BASE + 0x0c) as *const u32;
+const GICC_EOIR: *mut u32 = (GICC_BASE + 0x10) as *mut u32;
+const VC_IRQ_BASE: u32 = 96;
+const VC_IRQ_COUNT: u32 = 64;
+const SPURIOUS_IRQ: u32 = 1023;
+pub const I2C_VC_IRQ: u32 = 53;
+pub const SPI_VC_IRQ: u32 = 54;
 
 #[no_mangle]
 #[link_section = ".text.handlers"]
-async unsafe fn irq_handler() -> () {
-    let ids: [u32; 5] = core::array::from_fn(|i| read_volatile(MIMOIRQ[i]));
-    if (ids[0] & !(0 << 15)).count_ones() > 0 {}
-    if ids[0] & 1 << 16 == 1 << 16 {}
-    if ids[0] & 1 << 17 == 1 << 17 {}
-    if ids[0] & 1 << 18 == 1 << 18 {}
-    if ids[0] & 1 << 19 == 1 << 19 {}
-    if ids[0] & 1 << 20 == 1 << 20 {}
-    if ids[0] & 1 << 21 == 1 << 21 {}
-    if ids[1].count_ones() > 0 {}
-    if ids[2].count_ones() > 0 {}
-    if ids[3].count_ones() > 0 {
-        VC_IRQ::call_driver_handler(ids[3])
+unsafe extern "C" fn irq_handler() {
+    // Reading IAR acknowledges interruption and gives its ID
+    let iar = read_volatile(GICC_IAR);
+    let id = iar & 0x3ff;
+    if id == SPURIOUS_IRQ {
+        return;
+    }
+    if (VC_IRQ_BASE..VC_IRQ_BASE + VC_IRQ_COUNT).contains(&id) {
+        VC_IRQ::call_driver_handler(id - VC_IRQ_BASE)
     }
-    if ids[4].count_ones() > 0 {}
+    write_volatile(GICC_EOIR, iar);
+}
+
+pub unsafe fn init_interrupt_controller() {
+    write_volatile(GICD_CTLR, 1);
+    // Lowest priority mask lets every interruption through
+    write_volatile(GICC_PMR, 0xff);
+    write_volatile(GICC_CTLR, 1);
+    // Distributor forwards only interruptions drivers enable later
+    crate::cpu::exceptions::unmask_irq();
+}
+
+// Routes VideoCore interruption to boot core and unmasks it in distributor
+pub unsafe fn enable_vc_irq(vc_id: u32) {
+    let id = VC_IRQ_BASE + vc_id;
+    write_volatile((GICD_ITARGETSR + id as usize) as *mut u8, 1);
+    write_volatile(
+        (GICD_ISENABLER + 4 * (id / 32) as usize) as *mut u32,
+        1 << (id % 32),
+    );
+}
+
+// VideoCore number of GIC interruption ID, as found in device tree
+pub fn vc_irq_from_gic(id: u32) -> Option<u32> {
+    (VC_IRQ_BASE..VC_IRQ_BASE + VC_IRQ_COUNT)
+        .contains(&id)
+        .then(|| id - VC_IRQ_BASE)
+}
+
+pub unsafe fn disable_vc_irq(vc_id: u32) {
+    let id = VC_IRQ_BASE + vc_id;
+    write_volatile(
+        (GICD_ICENABLER + 4 * (id / 32) as usize) as *mut u32,
+        1 << (id % 32),
+    );
 }
+
 #[allow(non_camel_case_types)]
 struct VC_IRQ;
 #[repr(u32)]
@@ -72,6 +115,8 @@ impl Handler for VC_IRQ {
     unsafe fn call_driver_handler(id: u32) {
         match id {
             29 => Self::call_aux_handler(id),
+            43 => Self::call_bsc_slave_handler(),
+            49..=52 => Self::call_gpio_handler(id),
             53 => Self::call_i2c_handler(id),
             54 => Self::call_spi_handler(id),
             57 => Self::call_uart_handler(id), // &'static UART_handler.handle()
@@ -88,6 +133,16 @@ impl VC_IRQ {
         }
     }
 
+    unsafe fn call_bsc_slave_handler() {
+        super::bsc_slave().handle_interrupt()
+    }
+
+    unsafe fn call_gpio_handler(id: u32) {
+        // gpio_int[0..2] are per bank and gpio_int[3] is shared,
+        // handler checks all event detect registers anyway
+        bcm2711_gpio::handle_event_interrupt();
+    }
+
     unsafe fn call_uart_handler(id: u32) {
         // -> &'static UART_handler
         let pactl_cs_register = read_volatile(PACTL_CS);
@@ -102,29 +157,39 @@ impl VC_IRQ {
     }
     unsafe fn call_spi_handler(id: u32) {
         let pactl_cs_register = read_volatile(PACTL_CS);
-        match pactl_cs_register {
-            id if pactl_cs_register == SPI_interfaces::SPI0 as u32 => {}
-            id if pactl_cs_register == SPI_interfaces::SPI1 as u32 => {}
-            id if pactl_cs_register == SPI_interfaces::SPI2 as u32 => {}
-            id if pactl_cs_register == SPI_interfaces::SPI3 as u32 => {}
-            id if pactl_cs_register == SPI_interfaces::SPI4 as u32 => {}
-            id if pactl_cs_register == SPI_interfaces::SPI5 as u32 => {}
-            id if pactl_cs_register == SPI_interfaces::SPI6 as u32 => {}
-            _ => {}
+        // Several controllers can be pending at once
+        for (bit, controller) in [
+            (SPI_interfaces::SPI0 as u32, SpiController::Spi0),
+            (SPI_interfaces::SPI3 as u32, SpiController::Spi3),
+            (SPI_interfaces::SPI4 as u32, SpiController::Spi4),
+            (SPI_interfaces::SPI5 as u32, SpiController::Spi5),
+            (SPI_interfaces::SPI6 as u32, SpiController::Spi6),
+        ] {
+            if pactl_cs_register & bit == 0 {
+                continue;
+            }
+            if let Some(spi) = super::spi_bus(controller) {
+                spi.handle_interrupt()
+            }
         }
     }
     unsafe fn call_i2c_handler(id: u32) {
         let pactl_cs_register = read_volatile(PACTL_CS);
-        match pactl_cs_register {
-            id if pactl_cs_register == I2C_interfaces::I2C0 as u32 => {}
-            id if pactl_cs_register == I2C_interfaces::I2C1 as u32 => {}
-            id if pactl_cs_register == I2C_interfaces::I2C2 as u32 => {}
-            id if pactl_cs_register == I2C_interfaces::I2C3 as u32 => {}
-            id if pactl_cs_register == I2C_interfaces::I2C4 as u32 => {}
-            id if pactl_cs_register == I2C_interfaces::I2C5 as u32 => {}
-            id if pactl_cs_register == I2C_interfaces::I2C6 as u32 => {}
-            id if pactl_cs_register == I2C_interfaces::I2C7 as u32 => {}
-            _ => {}
+        // BSC2 and BSC7 belong to HDMI and are never registered
+        for (bit, bus) in [
+            (I2C_interfaces::I2C0 as u32, 0),
+            (I2C_interfaces::I2C1 as u32, 1),
+            (I2C_interfaces::I2C3 as u32, 3),
+            (I2C_interfaces::I2C4 as u32, 4),
+            (I2C_interfaces::I2C5 as u32, 5),
+            (I2C_interfaces::I2C6 as u32, 6),
+        ] {
+            if pactl_cs_register & bit == 0 {
+                continue;
+            }
+            if let Some(i2c) = super::i2c_bus(bus) {
+                i2c.handle_interrupt()
+            }
         }
     }
 }
@@ -0,0 +1,374 @@
+use core::fmt;
+use core::sync::atomic::{fence, Ordering};
+
+use crate::{
+    bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    registers,
+    synchronization::{interface::Mutex, NullLock},
+};
+
+use super::{InitDriverTrait, MutexControll};
+
+registers!(
+    (
+        REGISTER_NAME(READ),
+        OFFSET(0x00),
+        PERM(Permission::ReadOnly)
+    ),
+    (
+        REGISTER_NAME(PEEK),
+        OFFSET(0x10),
+        PERM(Permission::ReadOnly)
+    ),
+    (
+        REGISTER_NAME(SENDER),
+        OFFSET(0x14),
+        PERM(Permission::ReadOnly)
+    ),
+    (
+        REGISTER_NAME(STATUS),
+        OFFSET(0x18),
+        PERM(Permission::ReadOnly)
+    ),
+    (
+        REGISTER_NAME(CONFIG),
+        OFFSET(0x1c),
+        PERM(Permission::ReadWrite)
+    ),
+    (
+        REGISTER_NAME(WRITE),
+        OFFSET(0x20),
+        PERM(Permission::WriteOnly)
+    )
+);
+impl RegisterInterface for Registers {}
+type RegisterMapped = MIMODerefWrapper<Registers>;
+
+const STATUS_FULL: u32 = 1 << 31;
+const STATUS_EMPTY: u32 = 1 << 30;
+// ARM to VideoCore property tags channel
+const PROPERTY_CHANNEL: u32 = 8;
+const REQUEST_CODE: u32 = 0;
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+const TAG_RESPONSE: u32 = 1 << 31;
+const END_TAG: u32 = 0;
+const MESSAGE_WORDS: usize = 64;
+const POLL_LIMIT: u32 = 10_000_000;
+
+pub const TAG_GET_FIRMWARE_REVISION: u32 = 0x0000_0001;
+pub const TAG_GET_BOARD_REVISION: u32 = 0x0001_0002;
+pub const TAG_GET_BOARD_MAC_ADDRESS: u32 = 0x0001_0003;
+pub const TAG_GET_BOARD_SERIAL: u32 = 0x0001_0004;
+pub const TAG_GET_ARM_MEMORY: u32 = 0x0001_0005;
+pub const TAG_GET_VC_MEMORY: u32 = 0x0001_0006;
+pub const TAG_GET_POWER_STATE: u32 = 0x0002_0001;
+pub const TAG_SET_POWER_STATE: u32 = 0x0002_8001;
+pub const TAG_GET_CLOCK_RATE: u32 = 0x0003_0002;
+pub const TAG_GET_MAX_CLOCK_RATE: u32 = 0x0003_0004;
+pub const TAG_SET_CLOCK_RATE: u32 = 0x0003_8002;
+pub const TAG_GET_TEMPERATURE: u32 = 0x0003_0006;
+pub const TAG_GET_MAX_TEMPERATURE: u32 = 0x0003_000a;
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum FirmwareClock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+    Hevc = 11,
+    Emmc2 = 12,
+    M2mc = 13,
+    PixelBvb = 14,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum PowerDevice {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MailboxError {
+    // Mailbox stayed full or empty for too long
+    Timeout,
+    // Firmware couldn't parse the message
+    RequestFailed(u32),
+    // Firmware didn't recognize or answer the tag
+    TagNotHandled(u32),
+    MessageTooLong,
+    // Power or clock device reported as missing
+    NoDevice,
+}
+
+impl fmt::Display for MailboxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MailboxError::Timeout => write!(f, "mailbox timeout"),
+            MailboxError::RequestFailed(code) => write!(f, "request failed with 0x{:08x}", code),
+            MailboxError::TagNotHandled(tag) => write!(f, "tag 0x{:08x} not handled", tag),
+            MailboxError::MessageTooLong => write!(f, "property message too long"),
+            MailboxError::NoDevice => write!(f, "device does not exist"),
+        }
+    }
+}
+
+// Property channel message, firmware requires 16 byte alignment as
+// lowest 4 bits of address carry channel number
+#[repr(C, align(16))]
+pub struct PropertyMessage {
+    words: [u32; MESSAGE_WORDS],
+    length: usize,
+}
+
+impl PropertyMessage {
+    pub const fn new() -> Self {
+        Self {
+            words: [0; MESSAGE_WORDS],
+            // Space for buffer size and request code
+            length: 2,
+        }
+    }
+
+    // Returns index of tag, used later to get its response
+    pub fn add_tag(
+        &mut self,
+        tag: u32,
+        request: &[u32],
+        response_words: usize,
+    ) -> Result<usize, MailboxError> {
+        let value_words = request.len().max(response_words);
+        // Tag header, values and end tag have to fit
+        if self.length + 3 + value_words + 1 > MESSAGE_WORDS {
+            return Err(MailboxError::MessageTooLong);
+        }
+        let index = self.length;
+        self.words[index] = tag;
+        self.words[index + 1] = (value_words * 4) as u32;
+        self.words[index + 2] = REQUEST_CODE;
+        for i in 0..value_words {
+            self.words[index + 3 + i] = request.get(i).copied().unwrap_or(0);
+        }
+        self.length += 3 + value_words;
+        Ok(index)
+    }
+
+    pub fn tag_response(&self, index: usize) -> Result<&[u32], MailboxError> {
+        let tag = self.words[index];
+        let code = self.words[index + 2];
+        if code & TAG_RESPONSE == 0 {
+            return Err(MailboxError::TagNotHandled(tag));
+        }
+        let value_words = self.words[index + 1] as usize / 4;
+        // Firmware reports full length of answer, which may not fit
+        let response_words = ((code & !TAG_RESPONSE) as usize).div_ceil(4).min(value_words);
+        Ok(&self.words[index + 3..index + 3 + response_words])
+    }
+
+    fn finish(&mut self) {
+        self.words[self.length] = END_TAG;
+        self.words[0] = ((self.length + 1) * 4) as u32;
+        self.words[1] = REQUEST_CODE;
+    }
+}
+
+pub struct MailboxInner {
+    registers: RegisterMapped,
+}
+
+impl MailboxInner {
+    const fn new(start_addr: usize) -> Self {
+        Self {
+            registers: unsafe { RegisterMapped::new(start_addr) },
+        }
+    }
+
+    unsafe fn wait_status(&self, flag: u32) -> Result<(), MailboxError> {
+        for _ in 0..POLL_LIMIT {
+            if self.registers.read_reg::<u32>(Registers::STATUS).unwrap() & flag == 0 {
+                return Ok(());
+            }
+        }
+        Err(MailboxError::Timeout)
+    }
+
+    unsafe fn call(&self, message: &mut PropertyMessage) -> Result<(), MailboxError> {
+        message.finish();
+        // MMU and caches are off, so firmware sees buffer by its physical address
+        let address = message.words.as_ptr() as usize as u32;
+        fence(Ordering::SeqCst);
+        self.wait_status(STATUS_FULL)?;
+        self.registers
+            .write_to_reg(Registers::WRITE, address | PROPERTY_CHANNEL)
+            .unwrap();
+        loop {
+            self.wait_status(STATUS_EMPTY)?;
+            let answer = self.registers.read_reg::<u32>(Registers::READ).unwrap();
+            // Other channels answers are not for us
+            if answer == address | PROPERTY_CHANNEL {
+                break;
+            }
+        }
+        fence(Ordering::SeqCst);
+        match message.words[1] {
+            RESPONSE_SUCCESS => Ok(()),
+            code => Err(MailboxError::RequestFailed(code)),
+        }
+    }
+}
+
+impl InitDriverTrait for MailboxInner {
+    unsafe fn init_driver(&mut self) {}
+    unsafe fn clear_driver(&mut self) {}
+}
+
+pub struct Mailbox {
+    pub inner: NullLock<MailboxInner>,
+}
+
+impl Mailbox {
+    pub const fn new(start_addr: usize) -> Self {
+        Self {
+            inner: NullLock::new(MailboxInner::new(start_addr)),
+        }
+    }
+
+    pub fn call(&self, message: &mut PropertyMessage) -> Result<(), MailboxError> {
+        self.inner.lock(|i| unsafe { i.call(message) })
+    }
+
+    // Single tag message, returns N first response words
+    pub fn property<const N: usize>(
+        &self,
+        tag: u32,
+        request: &[u32],
+    ) -> Result<[u32; N], MailboxError> {
+        let mut message = PropertyMessage::new();
+        let index = message.add_tag(tag, request, N)?;
+        self.call(&mut message)?;
+        let response = message.tag_response(index)?;
+        let mut values = [0u32; N];
+        for (value, word) in values.iter_mut().zip(response) {
+            *value = *word
+        }
+        Ok(values)
+    }
+
+    pub fn get_firmware_revision(&self) -> Result<u32, MailboxError> {
+        Ok(self.property::<1>(TAG_GET_FIRMWARE_REVISION, &[])?[0])
+    }
+
+    pub fn get_board_revision(&self) -> Result<u32, MailboxError> {
+        Ok(self.property::<1>(TAG_GET_BOARD_REVISION, &[])?[0])
+    }
+
+    pub fn get_board_serial(&self) -> Result<u64, MailboxError> {
+        let [low, high] = self.property::<2>(TAG_GET_BOARD_SERIAL, &[])?;
+        Ok(((high as u64) << 32) | low as u64)
+    }
+
+    pub fn get_mac_address(&self) -> Result<[u8; 6], MailboxError> {
+        let [low, high] = self.property::<2>(TAG_GET_BOARD_MAC_ADDRESS, &[])?;
+        let low = low.to_le_bytes();
+        let high = high.to_le_bytes();
+        Ok([low[0], low[1], low[2], low[3], high[0], high[1]])
+    }
+
+    // Base address and size in bytes
+    pub fn get_arm_memory(&self) -> Result<(u32, u32), MailboxError> {
+        let [base, size] = self.property::<2>(TAG_GET_ARM_MEMORY, &[])?;
+        Ok((base, size))
+    }
+
+    pub fn get_vc_memory(&self) -> Result<(u32, u32), MailboxError> {
+        let [base, size] = self.property::<2>(TAG_GET_VC_MEMORY, &[])?;
+        Ok((base, size))
+    }
+
+    pub fn get_clock_rate(&self, clock: FirmwareClock) -> Result<u32, MailboxError> {
+        let [_, rate] = self.property::<2>(TAG_GET_CLOCK_RATE, &[clock as u32])?;
+        if rate == 0 {
+            return Err(MailboxError::NoDevice);
+        }
+        Ok(rate)
+    }
+
+    pub fn get_max_clock_rate(&self, clock: FirmwareClock) -> Result<u32, MailboxError> {
+        let [_, rate] = self.property::<2>(TAG_GET_MAX_CLOCK_RATE, &[clock as u32])?;
+        if rate == 0 {
+            return Err(MailboxError::NoDevice);
+        }
+        Ok(rate)
+    }
+
+    // Returns rate set by firmware, which may be rounded
+    pub fn set_clock_rate(
+        &self,
+        clock: FirmwareClock,
+        rate: u32,
+        skip_turbo: bool,
+    ) -> Result<u32, MailboxError> {
+        let [_, rate] = self.property::<2>(
+            TAG_SET_CLOCK_RATE,
+            &[clock as u32, rate, skip_turbo as u32],
+        )?;
+        if rate == 0 {
+            return Err(MailboxError::NoDevice);
+        }
+        Ok(rate)
+    }
+
+    // SoC temperature in thousandths of degree Celsius
+    pub fn get_temperature(&self) -> Result<u32, MailboxError> {
+        Ok(self.property::<2>(TAG_GET_TEMPERATURE, &[0])?[1])
+    }
+
+    pub fn get_max_temperature(&self) -> Result<u32, MailboxError> {
+        Ok(self.property::<2>(TAG_GET_MAX_TEMPERATURE, &[0])?[1])
+    }
+
+    pub fn get_power_state(&self, device: PowerDevice) -> Result<bool, MailboxError> {
+        let [_, state] = self.property::<2>(TAG_GET_POWER_STATE, &[device as u32])?;
+        // Bit 1 is set when device does not exist
+        if state & 0b10 != 0 {
+            return Err(MailboxError::NoDevice);
+        }
+        Ok(state & 1 != 0)
+    }
+
+    // With wait firmware returns only after device is stable
+    pub fn set_power_state(
+        &self,
+        device: PowerDevice,
+        on: bool,
+        wait: bool,
+    ) -> Result<bool, MailboxError> {
+        let request = on as u32 | ((wait as u32) << 1);
+        let [_, state] = self.property::<2>(TAG_SET_POWER_STATE, &[device as u32, request])?;
+        if state & 0b10 != 0 {
+            return Err(MailboxError::NoDevice);
+        }
+        Ok(state & 1 != 0)
+    }
+}
+
+impl MutexControll for Mailbox {
+    type M = NullLock<MailboxInner>;
+    unsafe fn get_inner(&mut self) -> &Self::M {
+        &self.inner
+    }
+}
@@ -0,0 +1,422 @@
+use core::fmt;
+
+use crate::{
+    bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    driver::{interface::DeviceDriver, DriverError},
+    registers,
+    synchronization::{interface::Mutex, NullLock},
+};
+
+use super::bcm2711_cm::{ClockError, ClockGenerator, ClockSource, Mash};
+use super::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use super::{clock_manager, InitDriverTrait, MutexControll};
+
+registers!(
+    (
+        REGISTER_NAME(CTL),
+        OFFSET(0x00),
+        PERM(Permission::ReadWrite)
+    ), // Control
+    (
+        REGISTER_NAME(STA),
+        OFFSET(0x04),
+        PERM(Permission::ReadWrite)
+    ), // Status
+    (
+        REGISTER_NAME(DMAC),
+        OFFSET(0x08),
+        PERM(Permission::ReadWrite)
+    ), // DMA configuration
+    (
+        REGISTER_NAME(RNG1),
+        OFFSET(0x10),
+        PERM(Permission::ReadWrite)
+    ), // Channel 1 range
+    (
+        REGISTER_NAME(DAT1),
+        OFFSET(0x14),
+        PERM(Permission::ReadWrite)
+    ), // Channel 1 data
+    (
+        REGISTER_NAME(FIF1),
+        OFFSET(0x18),
+        PERM(Permission::WriteOnly)
+    ), // FIFO input, shared by both channels
+    (
+        REGISTER_NAME(RNG2),
+        OFFSET(0x20),
+        PERM(Permission::ReadWrite)
+    ), // Channel 2 range
+    (
+        REGISTER_NAME(DAT2),
+        OFFSET(0x24),
+        PERM(Permission::ReadWrite)
+    )  // Channel 2 data
+);
+impl RegisterInterface for Registers {}
+type RegisterMapped = MIMODerefWrapper<Registers>;
+
+// Control register, channel 2 uses the same layout shifted by 8 bits
+const CTL_PWEN: u32 = 1 << 0;
+const CTL_MODE: u32 = 1 << 1;
+const CTL_RPTL: u32 = 1 << 2;
+const CTL_SBIT: u32 = 1 << 3;
+const CTL_POLA: u32 = 1 << 4;
+const CTL_USEF: u32 = 1 << 5;
+const CTL_CLRF: u32 = 1 << 6;
+const CTL_MSEN: u32 = 1 << 7;
+const CTL_CHANNEL_MASK: u32 = 0xbf;
+
+// Status register
+const STA_FULL: u32 = 1 << 0;
+const STA_WERR: u32 = 1 << 2;
+const STA_BERR: u32 = 1 << 8;
+
+const DEFAULT_CLOCK_RATE: u32 = 1_000_000;
+const POLL_LIMIT: u32 = 1_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PwmController {
+    Pwm0,
+    Pwm1,
+}
+
+impl PwmController {
+    pub const fn base_address(&self) -> usize {
+        match self {
+            PwmController::Pwm0 => 0x0_FE20_C000,
+            PwmController::Pwm1 => 0x0_FE20_C800,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PwmChannel {
+    One,
+    Two,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PwmMode {
+    // Pulses spread evenly over the range
+    Pwm,
+    // Output high for data ticks, then low till end of range
+    MarkSpace,
+    // Data or FIFO words are shifted out MSB first, range bits each
+    Serializer,
+}
+
+#[derive(Clone, Copy)]
+pub struct PwmConfig {
+    pub mode: PwmMode,
+    pub range: u32,
+    pub data: u32,
+    pub inverted: bool,
+    // Take data from FIFO instead of data register
+    pub use_fifo: bool,
+    // Repeat last FIFO word when FIFO runs empty
+    pub repeat_last: bool,
+    // Output level between transmissions
+    pub silence_high: bool,
+}
+
+impl PwmConfig {
+    pub const fn mark_space(range: u32, data: u32) -> Self {
+        Self {
+            mode: PwmMode::MarkSpace,
+            range,
+            data,
+            inverted: false,
+            use_fifo: false,
+            repeat_last: false,
+            silence_high: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PwmError {
+    // Pin can't be routed to this controller channel
+    InvalidPin(u32),
+    Gpio(GPIOError),
+    Clock(ClockError),
+    // FIFO write while full or bus error reported in STA
+    Fifo,
+}
+
+impl fmt::Display for PwmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PwmError::InvalidPin(pin) => write!(f, "GPIO {} has no PWM function", pin),
+            PwmError::Gpio(error) => write!(f, "{}", error),
+            PwmError::Clock(error) => write!(f, "{}", error),
+            PwmError::Fifo => write!(f, "PWM FIFO error"),
+        }
+    }
+}
+
+impl From<GPIOError> for PwmError {
+    fn from(error: GPIOError) -> Self {
+        PwmError::Gpio(error)
+    }
+}
+
+impl From<ClockError> for PwmError {
+    fn from(error: ClockError) -> Self {
+        PwmError::Clock(error)
+    }
+}
+
+// Both controllers are fed by one PWM clock generator
+fn configure_pwm_clock(rate: u32) -> Result<u32, PwmError> {
+    Ok(clock_manager().set_rate(
+        ClockGenerator::Pwm,
+        ClockSource::Oscillator,
+        rate,
+        Mash::Stage1,
+    )?)
+}
+
+// GPIO routing of every controller channel
+const PWM_PINS: [(PwmController, PwmChannel, u32, GPIOFunction); 7] = [
+    (PwmController::Pwm0, PwmChannel::One, 12, GPIOFunction::Alt0),
+    (PwmController::Pwm0, PwmChannel::One, 18, GPIOFunction::Alt5),
+    (PwmController::Pwm0, PwmChannel::Two, 13, GPIOFunction::Alt0),
+    (PwmController::Pwm0, PwmChannel::Two, 19, GPIOFunction::Alt5),
+    (PwmController::Pwm0, PwmChannel::Two, 45, GPIOFunction::Alt0),
+    (PwmController::Pwm1, PwmChannel::One, 40, GPIOFunction::Alt0),
+    (PwmController::Pwm1, PwmChannel::Two, 41, GPIOFunction::Alt0),
+];
+
+pub struct PwmInner {
+    registers: RegisterMapped,
+    controller: PwmController,
+    pins: [Option<GPIODriver>; 2],
+}
+
+impl PwmInner {
+    const fn new(controller: PwmController) -> Self {
+        Self {
+            registers: unsafe { RegisterMapped::new(controller.base_address()) },
+            controller,
+            pins: [None, None],
+        }
+    }
+
+    fn channel_shift(channel: PwmChannel) -> u32 {
+        match channel {
+            PwmChannel::One => 0,
+            PwmChannel::Two => 8,
+        }
+    }
+
+    fn range_reg(channel: PwmChannel) -> Register {
+        match channel {
+            PwmChannel::One => Registers::RNG1,
+            PwmChannel::Two => Registers::RNG2,
+        }
+    }
+
+    fn data_reg(channel: PwmChannel) -> Register {
+        match channel {
+            PwmChannel::One => Registers::DAT1,
+            PwmChannel::Two => Registers::DAT2,
+        }
+    }
+
+    unsafe fn route_pin(&mut self, channel: PwmChannel, pin: u32) -> Result<(), PwmError> {
+        let (_, _, _, function) = PWM_PINS
+            .iter()
+            .find(|(controller, pin_channel, pwm_pin, _)| {
+                *controller == self.controller && *pin_channel == channel && *pwm_pin == pin
+            })
+            .ok_or(PwmError::InvalidPin(pin))?;
+        let index = Self::channel_shift(channel) as usize / 8;
+        // Old pin goes back to registry before new one is claimed
+        self.pins[index] = None;
+        self.pins[index] = Some(GPIODriver::claim(
+            pin,
+            *function,
+            PullResistor::None,
+            "pwm",
+        )?);
+        Ok(())
+    }
+
+    unsafe fn enable_channel(&mut self, channel: PwmChannel, config: PwmConfig) {
+        let shift = Self::channel_shift(channel);
+        self.disable_channel(channel);
+        self.registers
+            .write_to_reg(Self::range_reg(channel), config.range)
+            .unwrap();
+        self.registers
+            .write_to_reg(Self::data_reg(channel), config.data)
+            .unwrap();
+        let mut bits = CTL_PWEN;
+        match config.mode {
+            PwmMode::Pwm => {}
+            PwmMode::MarkSpace => bits |= CTL_MSEN,
+            PwmMode::Serializer => bits |= CTL_MODE,
+        }
+        if config.inverted {
+            bits |= CTL_POLA
+        }
+        if config.use_fifo {
+            bits |= CTL_USEF
+        }
+        if config.repeat_last {
+            bits |= CTL_RPTL
+        }
+        if config.silence_high {
+            bits |= CTL_SBIT
+        }
+        let state = self.registers.read_reg::<u32>(Registers::CTL).unwrap();
+        self.registers
+            .write_to_reg(Registers::CTL, state | (bits << shift))
+            .unwrap();
+    }
+
+    unsafe fn disable_channel(&self, channel: PwmChannel) {
+        let state = self.registers.read_reg::<u32>(Registers::CTL).unwrap();
+        self.registers
+            .write_to_reg(
+                Registers::CTL,
+                state & !(CTL_CHANNEL_MASK << Self::channel_shift(channel)),
+            )
+            .unwrap();
+    }
+
+    unsafe fn clear_fifo(&self) {
+        let state = self.registers.read_reg::<u32>(Registers::CTL).unwrap();
+        self.registers
+            .write_to_reg(Registers::CTL, state | CTL_CLRF)
+            .unwrap();
+    }
+
+    unsafe fn write_fifo(&self, words: &[u32]) -> Result<(), PwmError> {
+        for word in words {
+            let mut polls = 0;
+            while self.registers.read_reg::<u32>(Registers::STA).unwrap() & STA_FULL != 0 {
+                polls += 1;
+                if polls == POLL_LIMIT {
+                    return Err(PwmError::Fifo);
+                }
+            }
+            self.registers.write_to_reg(Registers::FIF1, *word).unwrap();
+        }
+        let status = self.registers.read_reg::<u32>(Registers::STA).unwrap();
+        if status & (STA_WERR | STA_BERR) != 0 {
+            // Error flags are cleared by writing 1
+            self.registers
+                .write_to_reg(Registers::STA, STA_WERR | STA_BERR)
+                .unwrap();
+            return Err(PwmError::Fifo);
+        }
+        Ok(())
+    }
+}
+
+impl InitDriverTrait for PwmInner {
+    unsafe fn init_driver(&mut self) {
+        self.disable_channel(PwmChannel::One);
+        self.disable_channel(PwmChannel::Two);
+        self.clear_fifo();
+    }
+    unsafe fn clear_driver(&mut self) {
+        self.disable_channel(PwmChannel::One);
+        self.disable_channel(PwmChannel::Two);
+        self.pins = [None, None];
+    }
+}
+
+pub struct Pwm {
+    pub inner: NullLock<PwmInner>,
+}
+
+impl Pwm {
+    pub const fn new(controller: PwmController) -> Self {
+        Self {
+            inner: NullLock::new(PwmInner::new(controller)),
+        }
+    }
+    // Clock generator is set up here as well, unless the other
+    // controller already runs it, and it may refuse the rate
+    pub unsafe fn init_driver(&self) -> Result<(), PwmError> {
+        self.inner.lock(|i| i.init_driver());
+        if clock_manager().rate(ClockGenerator::Pwm) == 0 {
+            configure_pwm_clock(DEFAULT_CLOCK_RATE)?;
+        }
+        Ok(())
+    }
+    // Clock is shared by PWM0 and PWM1, so it affects channels of both.
+    // Returns rate actually generated
+    pub fn set_clock_rate(&self, rate: u32) -> Result<u32, PwmError> {
+        configure_pwm_clock(rate)
+    }
+    // Read back from generator, so both controllers see the same rate
+    pub fn clock_rate(&self) -> u32 {
+        clock_manager().rate(ClockGenerator::Pwm)
+    }
+    // Routes pin to channel with its alternative function and starts output
+    pub fn enable_channel(
+        &self,
+        channel: PwmChannel,
+        pin: u32,
+        config: PwmConfig,
+    ) -> Result<(), PwmError> {
+        self.inner.lock(|i| unsafe {
+            i.route_pin(channel, pin)?;
+            i.enable_channel(channel, config);
+            Ok(())
+        })
+    }
+    pub fn disable_channel(&self, channel: PwmChannel) {
+        self.inner.lock(|i| unsafe {
+            i.disable_channel(channel);
+            i.pins[PwmInner::channel_shift(channel) as usize / 8] = None;
+        })
+    }
+    pub fn set_range(&self, channel: PwmChannel, range: u32) {
+        self.inner.lock(|i| unsafe {
+            i.registers
+                .write_to_reg(PwmInner::range_reg(channel), range)
+                .unwrap();
+        })
+    }
+    pub fn set_data(&self, channel: PwmChannel, data: u32) {
+        self.inner.lock(|i| unsafe {
+            i.registers
+                .write_to_reg(PwmInner::data_reg(channel), data)
+                .unwrap();
+        })
+    }
+    pub fn clear_fifo(&self) {
+        self.inner.lock(|i| unsafe { i.clear_fifo() })
+    }
+    // Blocks while FIFO is full
+    pub fn write_fifo(&self, words: &[u32]) -> Result<(), PwmError> {
+        self.inner.lock(|i| unsafe { i.write_fifo(words) })
+    }
+}
+
+impl DeviceDriver for Pwm {
+    fn name(&self) -> &'static str {
+        self.inner.lock(|i| match i.controller {
+            PwmController::Pwm0 => "pwm0",
+            PwmController::Pwm1 => "pwm1",
+        })
+    }
+    fn compatible(&self) -> &'static str {
+        "brcm,bcm2835-pwm"
+    }
+    unsafe fn init(&self) -> Result<(), DriverError> {
+        Ok(self.init_driver()?)
+    }
+}
+
+impl MutexControll for Pwm {
+    type M = NullLock<PwmInner>;
+    unsafe fn get_inner(&mut self) -> &Self::M {
+        &self.inner
+    }
+}
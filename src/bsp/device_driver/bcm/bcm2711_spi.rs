@@ -0,0 +1,555 @@
+use crate::{
+    bsp::bus::{SpiBus, SpiError, SpiMode},
+    bsp::clock::{rate_of, ClockId},
+    bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    driver::{interface::DeviceDriver, DriverError},
+    registers,
+    synchronization::{interface::Mutex, NullLock},
+};
+
+use super::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use super::bcm2711_irq::SPI_VC_IRQ;
+use super::{InitDriverTrait, MutexControll};
+
+registers!(
+    (REGISTER_NAME(CS), OFFSET(0x00), PERM(Permission::ReadWrite)), // Control and status
+    (
+        REGISTER_NAME(FIFO),
+        OFFSET(0x04),
+        PERM(Permission::ReadWrite)
+    ), // TX and RX FIFO
+    (
+        REGISTER_NAME(CLK),
+        OFFSET(0x08),
+        PERM(Permission::ReadWrite)
+    ), // Clock divider
+    (
+        REGISTER_NAME(DLEN),
+        OFFSET(0x0c),
+        PERM(Permission::ReadWrite)
+    ), // Data length, DMA mode only
+    (
+        REGISTER_NAME(LTOH),
+        OFFSET(0x10),
+        PERM(Permission::ReadWrite)
+    ), // LoSSI output hold delay
+    (REGISTER_NAME(DC), OFFSET(0x14), PERM(Permission::ReadWrite)) // DMA DREQ controls
+);
+impl RegisterInterface for Registers {}
+type RegisterMapped = MIMODerefWrapper<Registers>;
+
+// Control and status register
+const CS_CS_MASK: u32 = 0b11;
+const CS_CPHA: u32 = 1 << 2;
+const CS_CPOL: u32 = 1 << 3;
+const CS_CLEAR_TX: u32 = 1 << 4;
+const CS_CLEAR_RX: u32 = 1 << 5;
+const CS_CSPOL: u32 = 1 << 6;
+const CS_TA: u32 = 1 << 7;
+const CS_INTD: u32 = 1 << 9;
+const CS_INTR: u32 = 1 << 10;
+const CS_DONE: u32 = 1 << 16;
+const CS_RXD: u32 = 1 << 17;
+const CS_TXD: u32 = 1 << 18;
+const CS_CSPOL0: u32 = 1 << 21;
+
+// Divider has to be even, 0 means 65536
+const MAX_DIVIDER: u32 = 65536;
+const POLL_LIMIT: u32 = 1_000_000;
+const FIFO_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpiController {
+    Spi0,
+    Spi3,
+    Spi4,
+    Spi5,
+    Spi6,
+}
+
+pub const SPI_CONTROLLERS: [SpiController; 5] = [
+    SpiController::Spi0,
+    SpiController::Spi3,
+    SpiController::Spi4,
+    SpiController::Spi5,
+    SpiController::Spi6,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChipSelect {
+    Cs0 = 0,
+    Cs1 = 1,
+}
+
+// Called from interrupt handler with buffer given to transfer_async,
+// filled with bytes clocked in
+pub type SpiCallback = fn(Result<&'static mut [u8], SpiError>);
+
+// Pin number and alternative function routing it to controller
+type SpiPin = (u32, GPIOFunction);
+
+struct SpiPins {
+    sclk: SpiPin,
+    mosi: SpiPin,
+    miso: SpiPin,
+    chip_selects: [SpiPin; 2],
+}
+
+impl SpiController {
+    pub const fn base_address(&self) -> usize {
+        match self {
+            SpiController::Spi0 => 0x0_FE20_4000,
+            SpiController::Spi3 => 0x0_FE20_4600,
+            SpiController::Spi4 => 0x0_FE20_4800,
+            SpiController::Spi5 => 0x0_FE20_4A00,
+            SpiController::Spi6 => 0x0_FE20_4C00,
+        }
+    }
+    fn owner(&self) -> &'static str {
+        match self {
+            SpiController::Spi0 => "spi0",
+            SpiController::Spi3 => "spi3",
+            SpiController::Spi4 => "spi4",
+            SpiController::Spi5 => "spi5",
+            SpiController::Spi6 => "spi6",
+        }
+    }
+    fn pins(&self) -> SpiPins {
+        use GPIOFunction::{Alt0, Alt3, Alt5};
+        // CE1 of SPI3-6 is on GPIO24-27, where it is ALT5
+        let (sclk, mosi, miso, chip_selects) = match self {
+            SpiController::Spi0 => ((11, Alt0), (10, Alt0), (9, Alt0), [(8, Alt0), (7, Alt0)]),
+            SpiController::Spi3 => ((3, Alt3), (2, Alt3), (1, Alt3), [(0, Alt3), (24, Alt5)]),
+            SpiController::Spi4 => ((7, Alt3), (6, Alt3), (5, Alt3), [(4, Alt3), (25, Alt5)]),
+            SpiController::Spi5 => ((15, Alt3), (14, Alt3), (13, Alt3), [(12, Alt3), (26, Alt5)]),
+            SpiController::Spi6 => ((21, Alt3), (20, Alt3), (19, Alt3), [(18, Alt3), (27, Alt5)]),
+        };
+        SpiPins {
+            sclk,
+            mosi,
+            miso,
+            chip_selects,
+        }
+    }
+}
+
+// State of running interrupt driven transfer
+struct AsyncTransfer {
+    buffer: &'static mut [u8],
+    written: usize,
+    read: usize,
+    callback: SpiCallback,
+}
+
+pub struct SpiInner {
+    registers: RegisterMapped,
+    controller: SpiController,
+    mode: SpiMode,
+    clock_rate: u32,
+    chip_select: ChipSelect,
+    // Chip select lines are active low unless set here
+    cs_active_high: [bool; 2],
+    pins: [Option<GPIODriver>; 5],
+    transfer: Option<AsyncTransfer>,
+    chars_written: usize,
+    chars_read: usize,
+}
+
+impl SpiInner {
+    const fn new(controller: SpiController, mode: SpiMode, clock_rate: u32) -> Self {
+        Self {
+            registers: unsafe { RegisterMapped::new(controller.base_address()) },
+            controller,
+            mode,
+            clock_rate,
+            chip_select: ChipSelect::Cs0,
+            cs_active_high: [false, false],
+            pins: [None, None, None, None, None],
+            transfer: None,
+            chars_written: 0,
+            chars_read: 0,
+        }
+    }
+
+    // Old pins go back to registry before new ones are claimed
+    fn claim_pins(&mut self) -> Result<(), GPIOError> {
+        let pins = self.controller.pins();
+        let owner = self.controller.owner();
+        let [cs0, cs1] = pins.chip_selects;
+        self.pins = [None, None, None, None, None];
+        for (slot, (pin, function)) in self
+            .pins
+            .iter_mut()
+            .zip([pins.sclk, pins.mosi, pins.miso, cs0, cs1])
+        {
+            *slot = Some(GPIODriver::claim(pin, function, PullResistor::None, owner)?);
+        }
+        Ok(())
+    }
+
+    // Returns rate actually generated
+    unsafe fn set_clock_rate(&mut self, rate: u32) -> u32 {
+        let core_rate = rate_of(ClockId::Core);
+        let mut divider = core_rate.div_ceil(rate.max(1));
+        divider = (divider + (divider & 1)).clamp(2, MAX_DIVIDER);
+        self.registers
+            .write_to_reg(Registers::CLK, divider % MAX_DIVIDER)
+            .unwrap();
+        self.clock_rate = core_rate / divider;
+        self.clock_rate
+    }
+
+    // Control register value for transfer with current settings
+    fn control(&self) -> u32 {
+        let mut control = self.chip_select as u32 & CS_CS_MASK;
+        if self.mode.cpha() {
+            control |= CS_CPHA
+        }
+        if self.mode.cpol() {
+            control |= CS_CPOL
+        }
+        for (i, active_high) in self.cs_active_high.iter().enumerate() {
+            if *active_high {
+                control |= CS_CSPOL0 << i
+            }
+        }
+        // Selected line idles at level given by CSPOL
+        if self.cs_active_high[self.chip_select as usize] {
+            control |= CS_CSPOL
+        }
+        control
+    }
+
+    unsafe fn status(&self) -> u32 {
+        self.registers.read_reg::<u32>(Registers::CS).unwrap()
+    }
+
+    unsafe fn begin(&self, interrupts: u32) {
+        let control = self.control();
+        self.registers
+            .write_to_reg(Registers::CS, control | CS_CLEAR_TX | CS_CLEAR_RX)
+            .unwrap();
+        self.registers
+            .write_to_reg(Registers::CS, control | interrupts | CS_TA)
+            .unwrap();
+    }
+
+    unsafe fn end(&self) {
+        self.registers
+            .write_to_reg(Registers::CS, self.control())
+            .unwrap();
+    }
+
+    // Polled full duplex transfer, byte of index i is produced by tx
+    // and byte clocked in at the same time is given to rx
+    unsafe fn exchange(
+        &mut self,
+        length: usize,
+        tx: impl Fn(usize) -> u8,
+        rx: impl FnMut(usize, u8),
+    ) -> Result<(), SpiError> {
+        if self.transfer.is_some() {
+            return Err(SpiError::Busy);
+        }
+        self.begin(0);
+        let result = self.shift(length, tx, rx);
+        self.end();
+        result
+    }
+
+    // Moves bytes of started transfer, chip select stays asserted
+    unsafe fn shift(
+        &mut self,
+        length: usize,
+        tx: impl Fn(usize) -> u8,
+        mut rx: impl FnMut(usize, u8),
+    ) -> Result<(), SpiError> {
+        let (mut written, mut read, mut polls) = (0, 0, 0);
+        while read < length {
+            let status = self.status();
+            if written < length && status & CS_TXD != 0 {
+                self.registers
+                    .write_to_reg(Registers::FIFO, tx(written) as u32)
+                    .unwrap();
+                written += 1;
+                polls = 0;
+            }
+            if status & CS_RXD != 0 {
+                rx(read, self.registers.read_reg::<u32>(Registers::FIFO).unwrap() as u8);
+                read += 1;
+                polls = 0;
+            }
+            polls += 1;
+            if polls == POLL_LIMIT {
+                return Err(SpiError::Timeout);
+            }
+        }
+        polls = 0;
+        while self.status() & CS_DONE == 0 {
+            polls += 1;
+            if polls == POLL_LIMIT {
+                return Err(SpiError::Timeout);
+            }
+        }
+        self.chars_written += length;
+        self.chars_read += length;
+        Ok(())
+    }
+
+    unsafe fn start_async(
+        &mut self,
+        buffer: &'static mut [u8],
+        callback: SpiCallback,
+    ) -> Result<(), SpiError> {
+        if self.transfer.is_some() {
+            return Err(SpiError::Busy);
+        }
+        self.transfer = Some(AsyncTransfer {
+            buffer,
+            written: 0,
+            read: 0,
+            callback,
+        });
+        // FIFO is empty, so DONE interruption fires right away and
+        // handler does the first fill
+        self.begin(CS_INTR | CS_INTD);
+        Ok(())
+    }
+
+    // Moves bytes between FIFO and buffer, returns finished transfer
+    unsafe fn handle_interrupt(&mut self) -> Option<AsyncTransfer> {
+        let registers = &self.registers;
+        let status = || registers.read_reg::<u32>(Registers::CS).unwrap();
+        let transfer = self.transfer.as_mut()?;
+        let length = transfer.buffer.len();
+        while transfer.read < length && status() & CS_RXD != 0 {
+            transfer.buffer[transfer.read] =
+                registers.read_reg::<u32>(Registers::FIFO).unwrap() as u8;
+            transfer.read += 1;
+        }
+        // Never queue more than can be received without RX overflow
+        while transfer.written < length
+            && transfer.written - transfer.read < FIFO_DEPTH
+            && status() & CS_TXD != 0
+        {
+            registers
+                .write_to_reg(Registers::FIFO, transfer.buffer[transfer.written] as u32)
+                .unwrap();
+            transfer.written += 1;
+        }
+        if transfer.read < length || status() & CS_DONE == 0 {
+            return None;
+        }
+        self.end();
+        self.chars_written += length;
+        self.chars_read += length;
+        self.transfer.take()
+    }
+}
+
+impl InitDriverTrait for SpiInner {
+    unsafe fn init_driver(&mut self) {
+        self.end();
+        self.set_clock_rate(self.clock_rate);
+    }
+    unsafe fn clear_driver(&mut self) {
+        self.registers
+            .write_to_reg(Registers::CS, CS_CLEAR_TX | CS_CLEAR_RX)
+            .unwrap();
+        self.transfer = None;
+        self.pins = [None, None, None, None, None];
+    }
+}
+
+pub struct Spi {
+    pub inner: NullLock<SpiInner>,
+}
+
+// Transfers made within Spi::transaction
+pub struct SpiTransaction<'a> {
+    inner: &'a mut SpiInner,
+}
+
+impl SpiTransaction<'_> {
+    // Same as Spi::transfer_split
+    pub fn transfer_split(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError> {
+        let length = read.len().max(write.len());
+        unsafe {
+            self.inner.shift(
+                length,
+                |n| write.get(n).copied().unwrap_or(0),
+                |n, byte| {
+                    if let Some(slot) = read.get_mut(n) {
+                        *slot = byte
+                    }
+                },
+            )
+        }
+    }
+    pub fn transfer(&mut self, data: &mut [u8]) -> Result<(), SpiError> {
+        let buffer = data.as_mut_ptr();
+        // Byte is always sent before the one received in its place is stored
+        unsafe {
+            self.inner.shift(
+                data.len(),
+                |n| *buffer.add(n),
+                |n, byte| *buffer.add(n) = byte,
+            )
+        }
+    }
+}
+
+impl Spi {
+    pub const fn new(controller: SpiController, mode: SpiMode, clock_rate: u32) -> Self {
+        Self {
+            inner: NullLock::new(SpiInner::new(controller, mode, clock_rate)),
+        }
+    }
+    pub unsafe fn init_driver(&self) -> Result<(), GPIOError> {
+        self.inner.lock(|i| {
+            i.claim_pins()?;
+            i.init_driver();
+            Ok(())
+        })
+    }
+    pub fn controller(&self) -> SpiController {
+        self.inner.lock(|i| i.controller)
+    }
+    // Returns rate actually generated, core clock divided by even number
+    pub fn set_clock_rate(&self, rate: u32) -> u32 {
+        self.inner.lock(|i| unsafe { i.set_clock_rate(rate) })
+    }
+    pub fn clock_rate(&self) -> u32 {
+        self.inner.lock(|i| i.clock_rate)
+    }
+    // Settings below rewrite CS register, which would stop running
+    // interrupt driven transfer
+    pub fn set_mode(&self, mode: SpiMode) -> Result<(), SpiError> {
+        self.inner.lock(|i| {
+            if i.transfer.is_some() {
+                return Err(SpiError::Busy);
+            }
+            i.mode = mode;
+            unsafe { i.end() };
+            Ok(())
+        })
+    }
+    // Line used by following transfers
+    pub fn select(&self, chip_select: ChipSelect) -> Result<(), SpiError> {
+        self.inner.lock(|i| {
+            if i.transfer.is_some() {
+                return Err(SpiError::Busy);
+            }
+            i.chip_select = chip_select;
+            unsafe { i.end() };
+            Ok(())
+        })
+    }
+    pub fn set_cs_polarity(
+        &self,
+        chip_select: ChipSelect,
+        active_high: bool,
+    ) -> Result<(), SpiError> {
+        self.inner.lock(|i| {
+            if i.transfer.is_some() {
+                return Err(SpiError::Busy);
+            }
+            i.cs_active_high[chip_select as usize] = active_high;
+            unsafe { i.end() };
+            Ok(())
+        })
+    }
+    // Chip select stays asserted for all transfers made by f, as
+    // commands of many devices are split into several parts
+    pub fn transaction(
+        &self,
+        f: impl FnOnce(&mut SpiTransaction) -> Result<(), SpiError>,
+    ) -> Result<(), SpiError> {
+        self.inner.lock(|i| unsafe {
+            if i.transfer.is_some() {
+                return Err(SpiError::Busy);
+            }
+            i.begin(0);
+            let result = f(&mut SpiTransaction { inner: &mut *i });
+            i.end();
+            result
+        })
+    }
+    // Starts full duplex transfer driven by interruptions,
+    // callback gets the buffer back once it is done
+    pub fn transfer_async(
+        &self,
+        buffer: &'static mut [u8],
+        callback: SpiCallback,
+    ) -> Result<(), SpiError> {
+        self.inner
+            .lock(|i| unsafe { i.start_async(buffer, callback) })
+    }
+    pub fn is_busy(&self) -> bool {
+        self.inner.lock(|i| i.transfer.is_some())
+    }
+    pub unsafe fn handle_interrupt(&self) {
+        // Callback runs outside of lock, so it can start next transfer
+        if let Some(transfer) = self.inner.lock(|i| i.handle_interrupt()) {
+            (transfer.callback)(Ok(transfer.buffer))
+        }
+    }
+    // Full duplex with separate buffers, bytes missing in write are sent
+    // as zeros and bytes past end of read are dropped
+    pub fn transfer_split(&self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError> {
+        let length = read.len().max(write.len());
+        self.inner.lock(|i| unsafe {
+            i.exchange(
+                length,
+                |n| write.get(n).copied().unwrap_or(0),
+                |n, byte| {
+                    if let Some(slot) = read.get_mut(n) {
+                        *slot = byte
+                    }
+                },
+            )
+        })
+    }
+}
+
+impl DeviceDriver for Spi {
+    fn name(&self) -> &'static str {
+        self.controller().owner()
+    }
+    fn compatible(&self) -> &'static str {
+        "brcm,bcm2835-spi"
+    }
+    unsafe fn init(&self) -> Result<(), DriverError> {
+        Ok(self.init_driver()?)
+    }
+    fn irq_number(&self) -> Option<u32> {
+        Some(SPI_VC_IRQ)
+    }
+}
+
+impl MutexControll for Spi {
+    type M = NullLock<SpiInner>;
+    unsafe fn get_inner(&mut self) -> &Self::M {
+        &self.inner
+    }
+}
+
+impl SpiBus for Spi {
+    fn transfer(&self, data: &mut [u8]) -> Result<(), SpiError> {
+        let buffer = data.as_mut_ptr();
+        // Byte is always sent before the one received in its place is stored
+        self.inner.lock(|i| unsafe {
+            i.exchange(data.len(), |n| *buffer.add(n), |n, byte| {
+                *buffer.add(n) = byte
+            })
+        })
+    }
+    fn write(&self, data: &[u8]) -> Result<(), SpiError> {
+        self.inner
+            .lock(|i| unsafe { i.exchange(data.len(), |n| data[n], |_, _| {}) })
+    }
+    fn read(&self, buffer: &mut [u8]) -> Result<(), SpiError> {
+        self.inner
+            .lock(|i| unsafe { i.exchange(buffer.len(), |_| 0, |n, byte| buffer[n] = byte) })
+    }
+}
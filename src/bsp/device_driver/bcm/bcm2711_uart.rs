@@ -1,36 +1,33 @@
 use crate::registers;
 use crate::{
     bsp::common::{MIMODerefWrapper, Permission, Register, RegisterInterface},
+    driver::{interface::DeviceDriver, DriverError},
     synchronization::NullLock,
 };
 
-use core::{
-    arch::asm,
-    fmt::{self, Write},
-};
-use fdt::Fdt;
+use core::fmt::{self, Write};
+
+use super::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use super::bcm2711_mailbox::FirmwareClock;
+use super::mailbox;
 
 static mut UART_CLOCK: u32 = 48_000_000;
 
-// Required ftd/dtb file with overlay assigning clock rate for uart clock
+pub fn uart_clock() -> u32 {
+    unsafe { UART_CLOCK }
+}
+
+// Default for when firmware doesn't answer, e.g. fixed clock of device tree
+pub unsafe fn set_default_uart_clock(rate: u32) {
+    UART_CLOCK = rate;
+}
+
+// UART clock is owned by firmware, default stays when it doesn't answer
 pub unsafe fn read_uart_clock() -> &'static u32 {
-    let dtb_pointer: *const u8 = 0x0 as *const u8;
-    asm!("ldr x0, adr_dtb
-            str x0, [{}]", in(reg) &dtb_pointer);
-    let dtb: Fdt = Fdt::from_ptr(dtb_pointer).expect("No dtb file");
-    if let Some(clk_rate_prop) = dtb
-        .find_phandle(0x43)
-        .unwrap_or_else(|| panic!("No UART config"))
-        .property("assigned-clocks-rates")
-    {
-        UART_CLOCK = clk_rate_prop
-            .as_usize()
-            .expect("Error while parsing clock value!") as u32;
-        &UART_CLOCK
-    } else {
-        crate::println!("Clock value is not appeared in dtb file. Default value loaded");
-        &UART_CLOCK
+    if let Ok(rate) = mailbox().get_clock_rate(FirmwareClock::Uart) {
+        UART_CLOCK = rate;
     }
+    &UART_CLOCK
 }
 
 pub enum ParityBit {
@@ -141,11 +138,29 @@ pub struct UartInner {
     word_length: WordLength,
     stop_bit: StopBits,
     baud_rate: u32,
+    // Pins stay claimed for as long as kernel runs
+    pins: Option<[GPIODriver; 2]>,
 }
 pub struct Uart {
     pub inner: NullLock<UartInner>,
 }
 
+impl DeviceDriver for Uart {
+    fn name(&self) -> &'static str {
+        "uart"
+    }
+    fn compatible(&self) -> &'static str {
+        "arm,pl011"
+    }
+    unsafe fn init(&self) -> Result<(), DriverError> {
+        self.inner.lock(|inner| {
+            inner.claim_pins()?;
+            inner.init_driver();
+            Ok(())
+        })
+    }
+}
+
 impl MutexControll for Uart {
     type M = NullLock<UartInner>;
     unsafe fn get_inner(&mut self) -> &Self::M {
@@ -172,6 +187,11 @@ impl crate::console::interface::Write for &mut Uart {
         self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
     }
 }
+impl crate::console::interface::Read for &mut Uart {
+    fn read_char(&self) -> Option<char> {
+        Some(self.inner.lock(|inner| inner.read_char()))
+    }
+}
 impl crate::console::interface::Statistics for &mut Uart {
     fn chars_written(&self) -> usize {
         self.inner.lock(|inner| inner.chars_written)
@@ -196,8 +216,16 @@ impl UartInner {
             word_length,
             stop_bit,
             baud_rate,
+            pins: None,
         }
     }
+    // Console UART0 is routed to TXD0/RXD0 on header pins 8/10
+    fn claim_pins(&mut self) -> Result<(), GPIOError> {
+        self.pins = None;
+        let claim = |pin| GPIODriver::claim(pin, GPIOFunction::Alt0, PullResistor::Up, "uart0");
+        self.pins = Some([claim(14)?, claim(15)?]);
+        Ok(())
+    }
     pub unsafe fn set_parity(&mut self, parity: Option<ParityBit>) {
         // If settings are made by hand
         if let Some(parity_present) = parity {
@@ -307,20 +335,62 @@ impl UartInner {
         self.registers.write_to_reg(Registers::IBRD, i_part);
         self.registers.write_to_reg(Registers::FBRD, f_part);
     }
-    pub unsafe fn read_data(&self, data: &mut [u8]) {}
-    pub unsafe fn write_data(&mut self, data: &[u8]) {
-        self.chars_written = 0;
+    // Blocks for the first byte, then takes what is already received
+    pub fn read_data(&mut self, data: &mut [u8]) -> usize {
+        let mut count = 0;
+        while count < data.len() && (count == 0 || self.rx_ready()) {
+            data[count] = self.read_byte();
+            count += 1;
+        }
+        count
+    }
+    pub fn write_data(&mut self, data: &[u8]) {
         for byte in data {
-            self.registers.write_to_reg(Registers::DR, byte);
-            self.chars_written += 1
+            self.write_byte(*byte)
+        }
+    }
+    pub fn rx_ready(&self) -> bool {
+        // Receive FIFO empty flag
+        unsafe { self.registers.read_reg::<u32>(Registers::FR).unwrap() & (1 << 4) == 0 }
+    }
+    pub fn read_byte(&mut self) -> u8 {
+        while !self.rx_ready() {
+            core::hint::spin_loop()
+        }
+        let data = unsafe { self.registers.read_reg::<u32>(Registers::DR).unwrap() };
+        self.chars_read += 1;
+        (data & 0xff) as u8
+    }
+    pub fn write_byte(&mut self, byte: u8) {
+        // Wait while transmit FIFO is full
+        while unsafe { self.registers.read_reg::<u32>(Registers::FR).unwrap() } & (1 << 5) != 0 {
+            core::hint::spin_loop()
+        }
+        unsafe {
+            self.registers
+                .write_to_reg(Registers::DR, byte as u32)
+                .unwrap()
+        }
+        self.chars_written += 1;
+    }
+    // Waits till last bit of transmit FIFO left the line
+    pub fn wait_tx_done(&self) {
+        while unsafe { self.registers.read_reg::<u32>(Registers::FR).unwrap() } & (1 << 3) != 0 {
+            core::hint::spin_loop()
         }
     }
     pub fn write_char(&mut self, c: char) {
         unsafe { self.registers.write_to_reg(Registers::DR, c).unwrap() }
         self.chars_written += 1;
     }
+    pub fn read_char(&mut self) -> char {
+        let c = self.read_byte() as char;
+        if c == 0x0d as char {
+            return 0x0a as char;
+        }
+        c
+    }
 }
-
 impl InitDriverTrait for UartInner {
     unsafe fn init_driver(&mut self) {
         // Read uart clock from ftd/dtb file
@@ -362,6 +432,15 @@ impl Uart {
             )),
         }
     }
+    pub fn write_bytes(&self, data: &[u8]) {
+        self.inner.lock(|inner| inner.write_data(data))
+    }
+    pub fn read_bytes(&self, buffer: &mut [u8]) -> usize {
+        self.inner.lock(|inner| inner.read_data(buffer))
+    }
+    pub fn wait_tx_done(&self) {
+        self.inner.lock(|inner| inner.wait_tx_done())
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -0,0 +1,18 @@
+// Protocols driven by toggling plain GPIO pins, for buses
+// on pins without hardware controller
+pub mod i2c;
+pub mod one_wire;
+pub mod spi;
+
+use super::bcm::bcm2711_gpio::{GPIODriver, GPIOFunction};
+
+// Open drain emulation: line is pulled low by driving output low
+// and released by switching pin to input, external pull-up does the rest
+fn release(line: &GPIODriver) {
+    line.set_function(GPIOFunction::Input)
+}
+
+fn pull_low(line: &GPIODriver) {
+    line.set_low();
+    line.set_function(GPIOFunction::Output)
+}
@@ -0,0 +1,173 @@
+use core::time::Duration;
+
+use super::{pull_low, release};
+use crate::bsp::bcm::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use crate::bsp::bus::{I2cBus, I2cError, SlaveAddress};
+use crate::time;
+
+// How long slave may hold SCL low before transfer is abandoned
+const CLOCK_STRETCH_TIMEOUT: Duration = Duration::from_millis(25);
+
+pub struct SoftI2c {
+    scl: GPIODriver,
+    sda: GPIODriver,
+    half_period: Duration,
+}
+
+impl SoftI2c {
+    pub fn new(
+        scl_pin: u32,
+        sda_pin: u32,
+        clock_rate: u32,
+        owner: &'static str,
+    ) -> Result<Self, GPIOError> {
+        let scl = GPIODriver::claim(scl_pin, GPIOFunction::Input, PullResistor::Up, owner)?;
+        let sda = GPIODriver::claim(sda_pin, GPIOFunction::Input, PullResistor::Up, owner)?;
+        Ok(Self {
+            scl,
+            sda,
+            half_period: Duration::from_nanos(500_000_000 / clock_rate as u64),
+        })
+    }
+
+    fn delay(&self) {
+        time::spin_for(self.half_period)
+    }
+
+    // Releases SCL and waits for slave to stop stretching the clock
+    fn scl_high(&self) -> Result<(), I2cError> {
+        release(&self.scl);
+        let start = time::uptime();
+        while self.scl.is_low() {
+            if time::uptime() - start > CLOCK_STRETCH_TIMEOUT {
+                return Err(I2cError::ClockStretchTimeout);
+            }
+        }
+        self.delay();
+        Ok(())
+    }
+
+    fn scl_low(&self) {
+        pull_low(&self.scl);
+        self.delay();
+    }
+
+    // Works as repeated start as well when SCL is low
+    fn start(&self) -> Result<(), I2cError> {
+        release(&self.sda);
+        self.scl_high()?;
+        pull_low(&self.sda);
+        self.delay();
+        self.scl_low();
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), I2cError> {
+        pull_low(&self.sda);
+        self.delay();
+        self.scl_high()?;
+        release(&self.sda);
+        self.delay();
+        Ok(())
+    }
+
+    fn write_bit(&self, bit: bool) -> Result<(), I2cError> {
+        if bit {
+            release(&self.sda)
+        } else {
+            pull_low(&self.sda)
+        }
+        self.scl_high()?;
+        // Released SDA held low means other master is talking
+        if bit && self.sda.is_low() {
+            return Err(I2cError::ArbitrationLost);
+        }
+        self.scl_low();
+        Ok(())
+    }
+
+    fn read_bit(&self) -> Result<bool, I2cError> {
+        release(&self.sda);
+        self.scl_high()?;
+        let bit = self.sda.is_high();
+        self.scl_low();
+        Ok(bit)
+    }
+
+    fn write_byte(&self, byte: u8) -> Result<(), I2cError> {
+        for i in (0..8).rev() {
+            self.write_bit(byte & (1 << i) != 0)?;
+        }
+        // Slave acknowledges by holding SDA low
+        match self.read_bit()? {
+            false => Ok(()),
+            true => Err(I2cError::Nack),
+        }
+    }
+
+    fn read_byte(&self, ack: bool) -> Result<u8, I2cError> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = (byte << 1) | self.read_bit()? as u8;
+        }
+        self.write_bit(!ack)?;
+        Ok(byte)
+    }
+
+    fn write_part(&self, address: SlaveAddress, data: &[u8]) -> Result<(), I2cError> {
+        let (header, low) = address.header()?;
+        self.write_byte(header << 1)?;
+        if let Some(low) = low {
+            self.write_byte(low)?;
+        }
+        for byte in data {
+            self.write_byte(*byte)?;
+        }
+        Ok(())
+    }
+
+    // After repeated start 10 bit slave needs only the header
+    fn read_part(&self, address: SlaveAddress, buffer: &mut [u8]) -> Result<(), I2cError> {
+        let (header, _) = address.header()?;
+        self.write_byte((header << 1) | 1)?;
+        let last = buffer.len().saturating_sub(1);
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            // Last byte is not acknowledged so slave releases SDA for stop
+            *byte = self.read_byte(i != last)?;
+        }
+        Ok(())
+    }
+
+    // Stop is sent whatever the transfer result is, so bus is left idle
+    fn finish(&self, result: Result<(), I2cError>) -> Result<(), I2cError> {
+        let stop = self.stop();
+        result.and(stop)
+    }
+}
+
+impl I2cBus for SoftI2c {
+    fn write(&self, address: SlaveAddress, data: &[u8]) -> Result<(), I2cError> {
+        self.start()?;
+        self.finish(self.write_part(address, data))
+    }
+    fn read(&self, address: SlaveAddress, buffer: &mut [u8]) -> Result<(), I2cError> {
+        if let SlaveAddress::TenBit(_) = address {
+            return self.write_read(address, &[], buffer);
+        }
+        self.start()?;
+        self.finish(self.read_part(address, buffer))
+    }
+    fn write_read(
+        &self,
+        address: SlaveAddress,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        self.start()?;
+        let result = self
+            .write_part(address, data)
+            .and_then(|_| self.start())
+            .and_then(|_| self.read_part(address, buffer));
+        self.finish(result)
+    }
+}
@@ -0,0 +1,68 @@
+use core::time::Duration;
+
+use super::{pull_low, release};
+use crate::bsp::bcm::bcm2711_gpio::{GPIODriver, GPIOError, GPIOFunction, PullResistor};
+use crate::bsp::bus::OneWireBus;
+use crate::time;
+
+// Standard speed timings in microseconds
+const RESET_LOW: u64 = 480;
+const PRESENCE_WAIT: u64 = 70;
+const RESET_RECOVERY: u64 = 410;
+const WRITE_ONE_LOW: u64 = 6;
+const WRITE_ONE_RECOVERY: u64 = 64;
+const WRITE_ZERO_LOW: u64 = 60;
+const WRITE_ZERO_RECOVERY: u64 = 10;
+const READ_LOW: u64 = 6;
+const READ_SAMPLE_WAIT: u64 = 9;
+const READ_RECOVERY: u64 = 55;
+
+fn delay_us(us: u64) {
+    time::spin_for(Duration::from_micros(us))
+}
+
+pub struct SoftOneWire {
+    line: GPIODriver,
+}
+
+impl SoftOneWire {
+    pub fn new(pin: u32, owner: &'static str) -> Result<Self, GPIOError> {
+        Ok(Self {
+            line: GPIODriver::claim(pin, GPIOFunction::Input, PullResistor::Up, owner)?,
+        })
+    }
+}
+
+impl OneWireBus for SoftOneWire {
+    fn reset(&self) -> bool {
+        pull_low(&self.line);
+        delay_us(RESET_LOW);
+        release(&self.line);
+        delay_us(PRESENCE_WAIT);
+        // Devices answer by holding line low
+        let presence = self.line.is_low();
+        delay_us(RESET_RECOVERY);
+        presence
+    }
+    fn write_bit(&self, bit: bool) {
+        pull_low(&self.line);
+        if bit {
+            delay_us(WRITE_ONE_LOW);
+            release(&self.line);
+            delay_us(WRITE_ONE_RECOVERY);
+        } else {
+            delay_us(WRITE_ZERO_LOW);
+            release(&self.line);
+            delay_us(WRITE_ZERO_RECOVERY);
+        }
+    }
+    fn read_bit(&self) -> bool {
+        pull_low(&self.line);
+        delay_us(READ_LOW);
+        release(&self.line);
+        delay_us(READ_SAMPLE_WAIT);
+        let bit = self.line.is_high();
+        delay_us(READ_RECOVERY);
+        bit
+    }
+}
@@ -0,0 +1,127 @@
+use core::time::Duration;
+
+use crate::bsp::bcm::bcm2711_gpio::{GPIOError, GPIOLevel, GpioPin, Input, Output, PullResistor};
+use crate::bsp::bus::{SpiBus, SpiError, SpiMode};
+use crate::time;
+
+pub struct SoftSpi {
+    sclk: GpioPin<Output>,
+    mosi: GpioPin<Output>,
+    miso: GpioPin<Input>,
+    // Active low, driven around every call when present
+    cs: Option<GpioPin<Output>>,
+    mode: SpiMode,
+    half_period: Duration,
+}
+
+impl SoftSpi {
+    pub fn new(
+        sclk_pin: u32,
+        mosi_pin: u32,
+        miso_pin: u32,
+        cs_pin: Option<u32>,
+        mode: SpiMode,
+        clock_rate: u32,
+        owner: &'static str,
+    ) -> Result<Self, GPIOError> {
+        let idle_level = if mode.cpol() {
+            GPIOLevel::High
+        } else {
+            GPIOLevel::Low
+        };
+        let cs = match cs_pin {
+            Some(pin) => Some(GpioPin::output(pin, GPIOLevel::High, owner)?),
+            None => None,
+        };
+        Ok(Self {
+            sclk: GpioPin::output(sclk_pin, idle_level, owner)?,
+            mosi: GpioPin::output(mosi_pin, GPIOLevel::Low, owner)?,
+            miso: GpioPin::input(miso_pin, PullResistor::None, owner)?,
+            cs,
+            mode,
+            half_period: Duration::from_nanos(500_000_000 / clock_rate as u64),
+        })
+    }
+
+    fn set_clock(&self, active: bool) {
+        // Active clock level is opposite to idle one
+        if active != self.mode.cpol() {
+            self.sclk.set_high()
+        } else {
+            self.sclk.set_low()
+        }
+    }
+
+    fn set_mosi(&self, bit: bool) {
+        if bit {
+            self.mosi.set_high()
+        } else {
+            self.mosi.set_low()
+        }
+    }
+
+    // MSB first, data is shifted out on one edge and sampled on the other
+    fn transfer_byte(&self, byte: u8) -> u8 {
+        let mut received = 0;
+        for i in (0..8).rev() {
+            let bit = byte & (1 << i) != 0;
+            if !self.mode.cpha() {
+                self.set_mosi(bit);
+                time::spin_for(self.half_period);
+                self.set_clock(true);
+                received = (received << 1) | self.miso.is_high() as u8;
+                time::spin_for(self.half_period);
+                self.set_clock(false);
+            } else {
+                self.set_clock(true);
+                self.set_mosi(bit);
+                time::spin_for(self.half_period);
+                self.set_clock(false);
+                received = (received << 1) | self.miso.is_high() as u8;
+                time::spin_for(self.half_period);
+            }
+        }
+        received
+    }
+
+    fn select(&self) {
+        if let Some(cs) = &self.cs {
+            cs.set_low();
+            time::spin_for(self.half_period);
+        }
+    }
+
+    fn deselect(&self) {
+        if let Some(cs) = &self.cs {
+            time::spin_for(self.half_period);
+            cs.set_high();
+        }
+    }
+}
+
+impl SpiBus for SoftSpi {
+    fn transfer(&self, data: &mut [u8]) -> Result<(), SpiError> {
+        self.select();
+        for byte in data.iter_mut() {
+            *byte = self.transfer_byte(*byte);
+        }
+        self.deselect();
+        Ok(())
+    }
+    fn write(&self, data: &[u8]) -> Result<(), SpiError> {
+        self.select();
+        for byte in data {
+            self.transfer_byte(*byte);
+        }
+        self.deselect();
+        Ok(())
+    }
+    fn read(&self, buffer: &mut [u8]) -> Result<(), SpiError> {
+        self.select();
+        for byte in buffer.iter_mut() {
+            *byte = self.transfer_byte(0);
+        }
+        self.deselect();
+        Ok(())
+    }
+}
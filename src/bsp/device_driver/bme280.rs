@@ -0,0 +1,180 @@
+// Bosch BME280 temperature, pressure and humidity sensor on I2C
+use super::bus::{I2cBus, I2cDevice};
+use crate::sensors::{interface::Sensor, Channel, SensorError, Unit};
+
+pub const BME280_ADDRESSES: [u8; 2] = [0x76, 0x77];
+const CHIP_ID: u8 = 0x60;
+
+// Registers
+const REG_CALIB_00: u8 = 0x88;
+const REG_CHIP_ID: u8 = 0xd0;
+const REG_CALIB_26: u8 = 0xe1;
+const REG_CTRL_HUM: u8 = 0xf2;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_CONFIG: u8 = 0xf5;
+const REG_PRESS_MSB: u8 = 0xf7;
+
+// Oversampling x1 on every channel, filter off, normal mode with
+// 1 s standby so readings are always at most a second old
+const CTRL_HUM: u8 = 0b001;
+const CTRL_MEAS: u8 = (0b001 << 5) | (0b001 << 2) | 0b11;
+const CONFIG: u8 = 0b101 << 5;
+
+static CHANNELS: [Channel; 3] = [
+    Channel {
+        name: "temperature",
+        unit: Unit::Celsius,
+    },
+    Channel {
+        name: "pressure",
+        unit: Unit::Hectopascal,
+    },
+    Channel {
+        name: "humidity",
+        unit: Unit::RelativeHumidity,
+    },
+];
+
+// Factory trimming values, named as in datasheet
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl Calibration {
+    fn parse(low: &[u8; 26], high: &[u8; 7]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([low[i], low[i + 1]]);
+        let i16_at = |i: usize| i16::from_le_bytes([low[i], low[i + 1]]);
+        Self {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p1: u16_at(6),
+            p2: i16_at(8),
+            p3: i16_at(10),
+            p4: i16_at(12),
+            p5: i16_at(14),
+            p6: i16_at(16),
+            p7: i16_at(18),
+            p8: i16_at(20),
+            p9: i16_at(22),
+            h1: low[25],
+            h2: i16::from_le_bytes([high[0], high[1]]),
+            h3: high[2],
+            // 12 bit values sharing nibbles of 0xe5
+            h4: ((high[3] as i8 as i16) << 4) | (high[4] & 0x0f) as i16,
+            h5: ((high[5] as i8 as i16) << 4) | (high[4] >> 4) as i16,
+            h6: high[6] as i8,
+        }
+    }
+
+    // Returns temperature in 0.01 C and t_fine used by other channels
+    fn temperature(&self, adc: i32) -> (i32, i32) {
+        let t1 = self.t1 as i32;
+        let var1 = (((adc >> 3) - (t1 << 1)) * self.t2 as i32) >> 11;
+        let var2 = (((((adc >> 4) - t1) * ((adc >> 4) - t1)) >> 12) * self.t3 as i32) >> 14;
+        let t_fine = var1 + var2;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    // Pressure in Pa as Q24.8
+    fn pressure(&self, adc: i32, t_fine: i32) -> u32 {
+        let mut var1 = t_fine as i64 - 128000;
+        let mut var2 = var1 * var1 * self.p6 as i64;
+        var2 += (var1 * self.p5 as i64) << 17;
+        var2 += (self.p4 as i64) << 35;
+        var1 = ((var1 * var1 * self.p3 as i64) >> 8) + ((var1 * self.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.p1 as i64) >> 33;
+        // Avoids division by zero on unprogrammed chip
+        if var1 == 0 {
+            return 0;
+        }
+        let mut p = 1048576 - adc as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        var1 = (self.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        var2 = (self.p8 as i64 * p) >> 19;
+        (((p + var1 + var2) >> 8) + ((self.p7 as i64) << 4)) as u32
+    }
+
+    // Relative humidity in % as Q22.10
+    fn humidity(&self, adc: i32, t_fine: i32) -> u32 {
+        let mut v = t_fine - 76800;
+        v = (((adc << 14) - ((self.h4 as i32) << 20) - (self.h5 as i32 * v) + 16384) >> 15)
+            * (((((((v * self.h6 as i32) >> 10) * (((v * self.h3 as i32) >> 11) + 32768)) >> 10)
+                + 2097152)
+                * self.h2 as i32
+                + 8192)
+                >> 14);
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * self.h1 as i32) >> 4;
+        (v.clamp(0, 419430400) >> 12) as u32
+    }
+}
+
+pub struct Bme280<'a, B: I2cBus> {
+    device: I2cDevice<'a, B>,
+    calibration: Calibration,
+}
+
+impl<'a, B: I2cBus> Bme280<'a, B> {
+    // Checks chip id, reads calibration and starts continuous measuring
+    pub fn probe(bus: &'a B, address: u8) -> Result<Self, SensorError> {
+        let device = I2cDevice::new(bus, address);
+        if device.read_reg8(REG_CHIP_ID)? != CHIP_ID {
+            return Err(SensorError::WrongDevice);
+        }
+        let mut low = [0u8; 26];
+        let mut high = [0u8; 7];
+        device.read_block(REG_CALIB_00, &mut low)?;
+        device.read_block(REG_CALIB_26, &mut high)?;
+        // Config is written only in sleep mode, humidity takes effect
+        // after write to ctrl_meas
+        device.write_reg8(REG_CONFIG, CONFIG)?;
+        device.write_reg8(REG_CTRL_HUM, CTRL_HUM)?;
+        device.write_reg8(REG_CTRL_MEAS, CTRL_MEAS)?;
+        Ok(Self {
+            device,
+            calibration: Calibration::parse(&low, &high),
+        })
+    }
+}
+
+impl<'a, B: I2cBus> Sensor for Bme280<'a, B> {
+    fn name(&self) -> &'static str {
+        "bme280"
+    }
+    fn channels(&self) -> &'static [Channel] {
+        &CHANNELS
+    }
+    fn sample(&self, values: &mut [f32]) -> Result<(), SensorError> {
+        if values.len() < CHANNELS.len() {
+            return Err(SensorError::BufferTooSmall);
+        }
+        // Burst read keeps all channels from the same measurement
+        let mut raw = [0u8; 8];
+        self.device.read_block(REG_PRESS_MSB, &mut raw)?;
+        let adc_p = ((raw[0] as i32) << 12) | ((raw[1] as i32) << 4) | (raw[2] as i32 >> 4);
+        let adc_t = ((raw[3] as i32) << 12) | ((raw[4] as i32) << 4) | (raw[5] as i32 >> 4);
+        let adc_h = ((raw[6] as i32) << 8) | raw[7] as i32;
+        let (temperature, t_fine) = self.calibration.temperature(adc_t);
+        values[0] = temperature as f32 / 100.0;
+        values[1] = self.calibration.pressure(adc_p, t_fine) as f32 / 256.0 / 100.0;
+        values[2] = self.calibration.humidity(adc_h, t_fine) as f32 / 1024.0;
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+// Bus interfaces shared by controller backed and bit-banged drivers,
+// so device drivers work with any of them
+pub use super::bcm::I2cError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlaveAddress {
+    SevenBit(u8),
+    TenBit(u16),
+}
+
+impl From<u8> for SlaveAddress {
+    fn from(address: u8) -> Self {
+        SlaveAddress::SevenBit(address)
+    }
+}
+
+impl SlaveAddress {
+    // 7 bit value sent with R/W bit in the first byte, and for 10 bit
+    // addresses the low address byte that follows 11110xx header
+    pub fn header(&self) -> Result<(u8, Option<u8>), I2cError> {
+        match *self {
+            SlaveAddress::SevenBit(address) if address <= 0x7f => Ok((address, None)),
+            SlaveAddress::TenBit(address) if address <= 0x3ff => {
+                Ok((0x78 | (address >> 8) as u8, Some(address as u8)))
+            }
+            _ => Err(I2cError::InvalidAddress),
+        }
+    }
+}
+
+pub trait I2cBus {
+    fn write(&self, address: SlaveAddress, data: &[u8]) -> Result<(), I2cError>;
+    fn read(&self, address: SlaveAddress, buffer: &mut [u8]) -> Result<(), I2cError>;
+    // Read follows write with repeated start
+    fn write_read(
+        &self,
+        address: SlaveAddress,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError>;
+}
+
+// Slave with 8 bit register pointer, 16 bit registers are big endian
+pub struct I2cDevice<'a, B: I2cBus> {
+    bus: &'a B,
+    address: SlaveAddress,
+}
+
+impl<'a, B: I2cBus> I2cDevice<'a, B> {
+    pub fn new(bus: &'a B, address: impl Into<SlaveAddress>) -> Self {
+        Self {
+            bus,
+            address: address.into(),
+        }
+    }
+    pub fn address(&self) -> SlaveAddress {
+        self.address
+    }
+    pub fn read_reg8(&self, register: u8) -> Result<u8, I2cError> {
+        let mut data = [0u8; 1];
+        self.bus.write_read(self.address, &[register], &mut data)?;
+        Ok(data[0])
+    }
+    pub fn read_reg16(&self, register: u8) -> Result<u16, I2cError> {
+        let mut data = [0u8; 2];
+        self.bus.write_read(self.address, &[register], &mut data)?;
+        Ok(u16::from_be_bytes(data))
+    }
+    pub fn write_reg8(&self, register: u8, value: u8) -> Result<(), I2cError> {
+        self.bus.write(self.address, &[register, value])
+    }
+    pub fn write_reg16(&self, register: u8, value: u16) -> Result<(), I2cError> {
+        let [high, low] = value.to_be_bytes();
+        self.bus.write(self.address, &[register, high, low])
+    }
+    // Raw message, register address goes first
+    pub fn write(&self, data: &[u8]) -> Result<(), I2cError> {
+        self.bus.write(self.address, data)
+    }
+    // Consecutive registers, slave increments its pointer on every byte
+    pub fn read_block(&self, register: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.bus.write_read(self.address, &[register], buffer)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpiMode {
+    // CPOL = 0, CPHA = 0
+    Mode0,
+    // CPOL = 0, CPHA = 1
+    Mode1,
+    // CPOL = 1, CPHA = 0
+    Mode2,
+    // CPOL = 1, CPHA = 1
+    Mode3,
+}
+impl SpiMode {
+    // Clock idles high
+    pub fn cpol(&self) -> bool {
+        matches!(self, SpiMode::Mode2 | SpiMode::Mode3)
+    }
+    // Data sampled on second clock edge
+    pub fn cpha(&self) -> bool {
+        matches!(self, SpiMode::Mode1 | SpiMode::Mode3)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpiError {
+    Timeout,
+    // Interrupt driven transfer is still running
+    Busy,
+}
+
+// Chip select is asserted for duration of single call
+pub trait SpiBus {
+    // Full duplex, every byte is replaced with byte clocked in
+    fn transfer(&self, data: &mut [u8]) -> Result<(), SpiError>;
+    fn write(&self, data: &[u8]) -> Result<(), SpiError>;
+    fn read(&self, buffer: &mut [u8]) -> Result<(), SpiError>;
+}
+
+pub const ONE_WIRE_SEARCH_ROM: u8 = 0xf0;
+pub const ONE_WIRE_MATCH_ROM: u8 = 0x55;
+pub const ONE_WIRE_SKIP_ROM: u8 = 0xcc;
+
+// State of ROM search, keep it between search_next calls
+#[derive(Clone, Copy, Default)]
+pub struct OneWireSearch {
+    rom: [u8; 8],
+    last_discrepancy: u8,
+    last_device: bool,
+}
+
+pub trait OneWireBus {
+    // Returns true when any device answered with presence pulse
+    fn reset(&self) -> bool;
+    fn write_bit(&self, bit: bool);
+    fn read_bit(&self) -> bool;
+
+    fn write_byte(&self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0)
+        }
+    }
+    fn read_byte(&self) -> u8 {
+        let mut byte = 0;
+        for i in 0..8 {
+            if self.read_bit() {
+                byte |= 1 << i
+            }
+        }
+        byte
+    }
+    // Addresses single device, following commands go only to it
+    fn select(&self, rom: &[u8; 8]) -> bool {
+        if !self.reset() {
+            return false;
+        }
+        self.write_byte(ONE_WIRE_MATCH_ROM);
+        for byte in rom {
+            self.write_byte(*byte)
+        }
+        true
+    }
+    // Addresses all devices on the bus at once
+    fn skip_rom(&self) -> bool {
+        if !self.reset() {
+            return false;
+        }
+        self.write_byte(ONE_WIRE_SKIP_ROM);
+        true
+    }
+    // Binary tree search from Maxim application note 187, every call
+    // returns next device ROM until all of them are found
+    fn search_next(&self, search: &mut OneWireSearch) -> Option<[u8; 8]> {
+        if search.last_device || !self.reset() {
+            *search = OneWireSearch::default();
+            return None;
+        }
+        self.write_byte(ONE_WIRE_SEARCH_ROM);
+        let mut last_zero = 0;
+        for bit_number in 1..=64u8 {
+            let byte = ((bit_number - 1) / 8) as usize;
+            let mask = 1 << ((bit_number - 1) % 8);
+            let id_bit = self.read_bit();
+            let complement_bit = self.read_bit();
+            // No device answered
+            if id_bit && complement_bit {
+                *search = OneWireSearch::default();
+                return None;
+            }
+            let direction = if id_bit != complement_bit {
+                id_bit
+            } else if bit_number < search.last_discrepancy {
+                search.rom[byte] & mask != 0
+            } else {
+                bit_number == search.last_discrepancy
+            };
+            if id_bit == complement_bit && !direction {
+                last_zero = bit_number;
+            }
+            if direction {
+                search.rom[byte] |= mask
+            } else {
+                search.rom[byte] &= !mask
+            }
+            self.write_bit(direction);
+        }
+        search.last_discrepancy = last_zero;
+        search.last_device = last_zero == 0;
+        if one_wire_crc8(&search.rom[..7]) != search.rom[7] {
+            *search = OneWireSearch::default();
+            return None;
+        }
+        Some(search.rom)
+    }
+}
+
+// Dallas/Maxim CRC-8, polynomial x^8 + x^5 + x^4 + 1
+pub fn one_wire_crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 1;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8c;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
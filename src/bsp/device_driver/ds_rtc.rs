@@ -0,0 +1,122 @@
+// Maxim DS3231 and DS1307 real-time clocks on I2C, both keep
+// time in the same BCD registers
+use super::bus::{I2cBus, I2cDevice};
+use crate::time::{interface::RealTimeClock, DateTime, RtcError};
+
+pub const DS_RTC_ADDRESS: u8 = 0x68;
+
+// Registers
+const REG_SECONDS: u8 = 0x00;
+const DS3231_REG_STATUS: u8 = 0x0f;
+
+// Flags
+const DS1307_CLOCK_HALT: u8 = 1 << 7;
+const HOUR_12H_MODE: u8 = 1 << 6;
+const HOUR_PM: u8 = 1 << 5;
+const DS3231_CENTURY: u8 = 1 << 7;
+const DS3231_OSCILLATOR_STOPPED: u8 = 1 << 7;
+
+// Both chips count years 00-99
+const FIRST_YEAR: u16 = 2000;
+const LAST_YEAR: u16 = 2099;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DsRtcModel {
+    Ds1307,
+    Ds3231,
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+pub struct DsRtc<'a, B: I2cBus> {
+    device: I2cDevice<'a, B>,
+    model: DsRtcModel,
+}
+
+impl<'a, B: I2cBus> DsRtc<'a, B> {
+    // Chips have no id register, reading seconds only checks presence
+    pub fn probe(bus: &'a B, model: DsRtcModel) -> Result<Self, RtcError> {
+        let device = I2cDevice::new(bus, DS_RTC_ADDRESS);
+        device.read_reg8(REG_SECONDS)?;
+        Ok(Self { device, model })
+    }
+
+    pub fn model(&self) -> DsRtcModel {
+        self.model
+    }
+
+    fn check_running(&self, seconds: u8) -> Result<(), RtcError> {
+        let stopped = match self.model {
+            DsRtcModel::Ds1307 => seconds & DS1307_CLOCK_HALT != 0,
+            DsRtcModel::Ds3231 => {
+                self.device.read_reg8(DS3231_REG_STATUS)? & DS3231_OSCILLATOR_STOPPED != 0
+            }
+        };
+        match stopped {
+            true => Err(RtcError::ClockStopped),
+            false => Ok(()),
+        }
+    }
+}
+
+impl<'a, B: I2cBus> RealTimeClock for DsRtc<'a, B> {
+    fn name(&self) -> &'static str {
+        match self.model {
+            DsRtcModel::Ds1307 => "ds1307",
+            DsRtcModel::Ds3231 => "ds3231",
+        }
+    }
+
+    fn read_time(&self) -> Result<DateTime, RtcError> {
+        // Burst read, chip latches all registers on start condition
+        let mut raw = [0u8; 7];
+        self.device.read_block(REG_SECONDS, &mut raw)?;
+        self.check_running(raw[0])?;
+        let hour = match raw[2] & HOUR_12H_MODE {
+            0 => from_bcd(raw[2] & 0x3f),
+            _ => from_bcd(raw[2] & 0x1f) % 12 + if raw[2] & HOUR_PM != 0 { 12 } else { 0 },
+        };
+        let time = DateTime {
+            year: FIRST_YEAR + from_bcd(raw[6]) as u16,
+            month: from_bcd(raw[5] & !DS3231_CENTURY),
+            day: from_bcd(raw[4] & 0x3f),
+            hour,
+            minute: from_bcd(raw[1] & 0x7f),
+            second: from_bcd(raw[0] & 0x7f),
+        };
+        match time.is_valid() {
+            true => Ok(time),
+            false => Err(RtcError::InvalidTime),
+        }
+    }
+
+    // Writes 24 hour mode, also restarts stopped oscillator
+    fn set_time(&self, time: &DateTime) -> Result<(), RtcError> {
+        if !time.is_valid() || !(FIRST_YEAR..=LAST_YEAR).contains(&time.year) {
+            return Err(RtcError::InvalidTime);
+        }
+        let data = [
+            REG_SECONDS,
+            to_bcd(time.second),
+            to_bcd(time.minute),
+            to_bcd(time.hour),
+            time.weekday(),
+            to_bcd(time.day),
+            to_bcd(time.month),
+            to_bcd((time.year - FIRST_YEAR) as u8),
+        ];
+        self.device.write(&data)?;
+        if self.model == DsRtcModel::Ds3231 {
+            let status = self.device.read_reg8(DS3231_REG_STATUS)?;
+            self.device
+                .write_reg8(DS3231_REG_STATUS, status & !DS3231_OSCILLATOR_STOPPED)?;
+        }
+        Ok(())
+    }
+}
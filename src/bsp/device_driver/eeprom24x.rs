@@ -0,0 +1,224 @@
+// 24Cxx serial EEPROMs on any I2C bus
+use core::fmt;
+use core::time::Duration;
+
+use super::bus::{I2cBus, I2cError, SlaveAddress};
+use crate::time;
+
+// Write cycle takes at most 5 ms on every part, some margin on top
+const WRITE_CYCLE_TIMEOUT: Duration = Duration::from_millis(20);
+const MAX_PAGE_SIZE: usize = 256;
+const MAX_ADDRESS_BYTES: usize = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Eeprom24xPart {
+    C01,
+    C02,
+    C04,
+    C08,
+    C16,
+    C32,
+    C64,
+    C128,
+    C256,
+    C512,
+}
+
+pub const EEPROM_24X_PARTS: [Eeprom24xPart; 10] = [
+    Eeprom24xPart::C01,
+    Eeprom24xPart::C02,
+    Eeprom24xPart::C04,
+    Eeprom24xPart::C08,
+    Eeprom24xPart::C16,
+    Eeprom24xPart::C32,
+    Eeprom24xPart::C64,
+    Eeprom24xPart::C128,
+    Eeprom24xPart::C256,
+    Eeprom24xPart::C512,
+];
+
+impl Eeprom24xPart {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Eeprom24xPart::C01 => "24c01",
+            Eeprom24xPart::C02 => "24c02",
+            Eeprom24xPart::C04 => "24c04",
+            Eeprom24xPart::C08 => "24c08",
+            Eeprom24xPart::C16 => "24c16",
+            Eeprom24xPart::C32 => "24c32",
+            Eeprom24xPart::C64 => "24c64",
+            Eeprom24xPart::C128 => "24c128",
+            Eeprom24xPart::C256 => "24c256",
+            Eeprom24xPart::C512 => "24c512",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        EEPROM_24X_PARTS
+            .iter()
+            .find(|part| part.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+    pub fn size(&self) -> usize {
+        match self {
+            Eeprom24xPart::C01 => 128,
+            Eeprom24xPart::C02 => 256,
+            Eeprom24xPart::C04 => 512,
+            Eeprom24xPart::C08 => 1024,
+            Eeprom24xPart::C16 => 2048,
+            Eeprom24xPart::C32 => 4096,
+            Eeprom24xPart::C64 => 8192,
+            Eeprom24xPart::C128 => 16384,
+            Eeprom24xPart::C256 => 32768,
+            Eeprom24xPart::C512 => 65536,
+        }
+    }
+    pub fn page_size(&self) -> usize {
+        match self {
+            Eeprom24xPart::C01 | Eeprom24xPart::C02 => 8,
+            Eeprom24xPart::C04 | Eeprom24xPart::C08 | Eeprom24xPart::C16 => 16,
+            Eeprom24xPart::C32 | Eeprom24xPart::C64 => 32,
+            Eeprom24xPart::C128 | Eeprom24xPart::C256 => 64,
+            Eeprom24xPart::C512 => 128,
+        }
+    }
+    // Parts up to 2 kB take 8 bit memory address, bits above it
+    // go to the lowest bits of slave address
+    pub fn address_bytes(&self) -> usize {
+        if self.size() <= 2048 {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EepromError {
+    I2c(I2cError),
+    // Access goes past end of memory
+    OutOfRange,
+    // Chip did not acknowledge after write cycle time
+    WriteTimeout,
+}
+
+impl fmt::Display for EepromError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EepromError::I2c(error) => write!(f, "{}", error),
+            EepromError::OutOfRange => write!(f, "access out of memory range"),
+            EepromError::WriteTimeout => write!(f, "write cycle timed out"),
+        }
+    }
+}
+
+impl From<I2cError> for EepromError {
+    fn from(error: I2cError) -> Self {
+        EepromError::I2c(error)
+    }
+}
+
+pub struct Eeprom24x<'a, B: I2cBus> {
+    bus: &'a B,
+    // Slave address with A0-A2 pins applied, usually 0x50
+    address: u8,
+    part: Eeprom24xPart,
+}
+
+impl<'a, B: I2cBus> Eeprom24x<'a, B> {
+    pub fn new(bus: &'a B, address: u8, part: Eeprom24xPart) -> Self {
+        Self { bus, address, part }
+    }
+
+    pub fn part(&self) -> Eeprom24xPart {
+        self.part
+    }
+
+    pub fn size(&self) -> usize {
+        self.part.size()
+    }
+
+    fn check_range(&self, offset: usize, length: usize) -> Result<(), EepromError> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.size() => Ok(()),
+            _ => Err(EepromError::OutOfRange),
+        }
+    }
+
+    // Slave address and memory address bytes of given offset
+    fn addressing(&self, offset: usize) -> (SlaveAddress, [u8; MAX_ADDRESS_BYTES], usize) {
+        match self.part.address_bytes() {
+            1 => (
+                SlaveAddress::SevenBit(self.address | (offset >> 8) as u8),
+                [offset as u8, 0],
+                1,
+            ),
+            _ => (
+                SlaveAddress::SevenBit(self.address),
+                [(offset >> 8) as u8, offset as u8],
+                2,
+            ),
+        }
+    }
+
+    // Sequential read, split where 8 bit addressed parts switch slave address
+    pub fn read(&self, offset: usize, buffer: &mut [u8]) -> Result<(), EepromError> {
+        self.check_range(offset, buffer.len())?;
+        let block = match self.part.address_bytes() {
+            1 => 256,
+            _ => self.size(),
+        };
+        let mut done = 0;
+        while done < buffer.len() {
+            let position = offset + done;
+            let length = (block - position % block).min(buffer.len() - done);
+            let (slave, memory_address, address_length) = self.addressing(position);
+            self.bus.write_read(
+                slave,
+                &memory_address[..address_length],
+                &mut buffer[done..done + length],
+            )?;
+            done += length;
+        }
+        Ok(())
+    }
+
+    // Split at page boundaries, as chip wraps around within a page
+    pub fn write(&self, offset: usize, data: &[u8]) -> Result<(), EepromError> {
+        self.check_range(offset, data.len())?;
+        let page_size = self.part.page_size();
+        let mut message = [0u8; MAX_ADDRESS_BYTES + MAX_PAGE_SIZE];
+        let mut done = 0;
+        while done < data.len() {
+            let position = offset + done;
+            let length = (page_size - position % page_size).min(data.len() - done);
+            let (slave, memory_address, address_length) = self.addressing(position);
+            message[..address_length].copy_from_slice(&memory_address[..address_length]);
+            message[address_length..address_length + length]
+                .copy_from_slice(&data[done..done + length]);
+            self.bus.write(slave, &message[..address_length + length])?;
+            self.wait_write_cycle(slave, &memory_address[..address_length])?;
+            done += length;
+        }
+        Ok(())
+    }
+
+    // Chip ignores its address while it programs the page
+    fn wait_write_cycle(
+        &self,
+        slave: SlaveAddress,
+        memory_address: &[u8],
+    ) -> Result<(), EepromError> {
+        let start = time::uptime();
+        loop {
+            // Sets address pointer only, nothing gets programmed
+            match self.bus.write(slave, memory_address) {
+                Ok(()) => return Ok(()),
+                Err(I2cError::Nack) => {}
+                Err(error) => return Err(error.into()),
+            }
+            if time::uptime() - start > WRITE_CYCLE_TIMEOUT {
+                return Err(EepromError::WriteTimeout);
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+// embedded-hal 1.0 and embedded-io implementations, so drivers from
+// the embedded Rust ecosystem run on top of ours.
+// Shared bus drivers live in statics, so traits are implemented for
+// references and any number of device drivers can hold the same bus
+use core::convert::Infallible;
+use core::time::Duration;
+
+use embedded_hal::{delay, digital, i2c, spi};
+
+use super::bcm::bcm2711_gpio::GPIODriver;
+use super::bcm::{AuxSpi, I2cError, Spi, Uart, I2C};
+use super::bus::{SlaveAddress, SpiError};
+use crate::time;
+
+//_____________________________________________________________
+//
+//  GPIO
+//_________________________________________
+//
+impl digital::ErrorType for GPIODriver {
+    type Error = Infallible;
+}
+
+impl digital::InputPin for GPIODriver {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(GPIODriver::is_high(self))
+    }
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(GPIODriver::is_low(self))
+    }
+}
+
+impl digital::OutputPin for GPIODriver {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        GPIODriver::set_low(self);
+        Ok(())
+    }
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        GPIODriver::set_high(self);
+        Ok(())
+    }
+}
+
+// Level register reflects the driven level of output pin
+impl digital::StatefulOutputPin for GPIODriver {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(GPIODriver::is_high(self))
+    }
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(GPIODriver::is_low(self))
+    }
+}
+
+//_____________________________________________________________
+//
+//  I2C
+//_________________________________________
+//
+impl i2c::Error for I2cError {
+    fn kind(&self) -> i2c::ErrorKind {
+        match self {
+            I2cError::Nack => i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Unknown),
+            I2cError::ArbitrationLost => i2c::ErrorKind::ArbitrationLoss,
+            I2cError::BusBusy | I2cError::ClockStretchTimeout => i2c::ErrorKind::Bus,
+            _ => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+impl i2c::ErrorType for &I2C {
+    type Error = I2cError;
+}
+
+// Adjacent operations of one kind are copied into one part through
+// this buffer, single operations are transferred in place
+const I2C_MERGE_BUFFER_SIZE: usize = 32;
+
+fn merge_writes<'b>(
+    writes: &'b [i2c::Operation<'_>],
+    buffer: &'b mut [u8; I2C_MERGE_BUFFER_SIZE],
+) -> Result<&'b [u8], I2cError> {
+    if let [i2c::Operation::Write(data)] = writes {
+        return Ok(data);
+    }
+    let mut length = 0;
+    for operation in writes {
+        if let i2c::Operation::Write(data) = operation {
+            buffer
+                .get_mut(length..length + data.len())
+                .ok_or(I2cError::TooLong)?
+                .copy_from_slice(data);
+            length += data.len();
+        }
+    }
+    Ok(&buffer[..length])
+}
+
+// Controller makes one write, one read or write and read joined by
+// repeated start, without stop in between. Other sequences, such as
+// write after read, are refused instead of being split by stop
+fn i2c_transaction(
+    bus: &I2C,
+    address: SlaveAddress,
+    operations: &mut [i2c::Operation<'_>],
+) -> Result<(), I2cError> {
+    let split = operations
+        .iter()
+        .position(|operation| matches!(operation, i2c::Operation::Read(_)))
+        .unwrap_or(operations.len());
+    let (writes, reads) = operations.split_at_mut(split);
+    if reads
+        .iter()
+        .any(|operation| matches!(operation, i2c::Operation::Write(_)))
+    {
+        return Err(I2cError::Unsupported);
+    }
+    let (write_only, read_only) = (reads.is_empty(), writes.is_empty());
+    let mut write_buffer = [0u8; I2C_MERGE_BUFFER_SIZE];
+    let data = merge_writes(writes, &mut write_buffer)?;
+    let transfer = |buffer: &mut [u8]| {
+        if write_only {
+            bus.write(address, data)
+        } else if read_only {
+            bus.read(address, buffer)
+        } else {
+            bus.write_then_read(address, data, buffer)
+        }
+    };
+    match reads {
+        [] if read_only => Ok(()),
+        [] => transfer(&mut []),
+        [i2c::Operation::Read(buffer)] => transfer(buffer),
+        _ => {
+            // Read as one part, then handed out to operations in order
+            let mut read_buffer = [0u8; I2C_MERGE_BUFFER_SIZE];
+            let length = reads.iter().fold(0, |length, operation| match operation {
+                i2c::Operation::Read(buffer) => length + buffer.len(),
+                _ => length,
+            });
+            transfer(read_buffer.get_mut(..length).ok_or(I2cError::TooLong)?)?;
+            let mut offset = 0;
+            for operation in reads {
+                if let i2c::Operation::Read(buffer) = operation {
+                    buffer.copy_from_slice(&read_buffer[offset..offset + buffer.len()]);
+                    offset += buffer.len();
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+impl i2c::I2c<i2c::SevenBitAddress> for &I2C {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        i2c_transaction(self, SlaveAddress::SevenBit(address), operations)
+    }
+}
+
+impl i2c::I2c<i2c::TenBitAddress> for &I2C {
+    fn transaction(
+        &mut self,
+        address: u16,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        i2c_transaction(self, SlaveAddress::TenBit(address), operations)
+    }
+}
+
+//_____________________________________________________________
+//
+//  SPI
+//_________________________________________
+//
+// Both controllers drive chip select themselves, so they are devices
+// rather than buses, the one selected by Spi::select or AuxSpi::select
+impl spi::Error for SpiError {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+impl spi::ErrorType for &Spi {
+    type Error = SpiError;
+}
+
+impl spi::ErrorType for &AuxSpi {
+    type Error = SpiError;
+}
+
+// Transaction types of controllers differ, methods they have are the same
+macro_rules! spi_operation {
+    ($transaction:expr, $operation:expr) => {
+        match $operation {
+            spi::Operation::Read(words) => $transaction.transfer_split(words, &[]),
+            spi::Operation::Write(words) => $transaction.transfer_split(&mut [], words),
+            spi::Operation::Transfer(read, write) => $transaction.transfer_split(read, write),
+            spi::Operation::TransferInPlace(words) => $transaction.transfer(words),
+            spi::Operation::DelayNs(ns) => {
+                time::spin_for(Duration::from_nanos(*ns as u64));
+                Ok(())
+            }
+        }
+    };
+}
+
+impl spi::SpiDevice for &Spi {
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        Spi::transaction(self, |transaction| {
+            for operation in operations {
+                spi_operation!(transaction, operation)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn moves_data(operation: &spi::Operation<'_, u8>) -> bool {
+    match operation {
+        spi::Operation::Read(words) => !words.is_empty(),
+        spi::Operation::Write(words) => !words.is_empty(),
+        spi::Operation::Transfer(read, write) => !read.is_empty() || !write.is_empty(),
+        spi::Operation::TransferInPlace(words) => !words.is_empty(),
+        spi::Operation::DelayNs(_) => false,
+    }
+}
+
+// Chip select goes up with the last byte, so delays after it
+// run with device already deselected
+impl spi::SpiDevice for &AuxSpi {
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let last = operations.iter().rposition(moves_data);
+        AuxSpi::transaction(self, |transaction| {
+            for (index, operation) in operations.iter_mut().enumerate() {
+                if Some(index) == last {
+                    transaction.release_after_next();
+                }
+                spi_operation!(transaction, operation)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+//_____________________________________________________________
+//
+//  UART
+//_________________________________________
+//
+impl embedded_io::ErrorType for &Uart {
+    type Error = Infallible;
+}
+
+impl embedded_io::Read for &Uart {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.read_bytes(buf))
+    }
+}
+
+impl embedded_io::Write for &Uart {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_bytes(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.wait_tx_done();
+        Ok(())
+    }
+}
+
+//_____________________________________________________________
+//
+//  DELAY
+//_________________________________________
+//
+// Busy waits on the generic timer
+pub struct Delay;
+
+impl delay::DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        time::spin_for(Duration::from_nanos(ns as u64))
+    }
+}
@@ -0,0 +1,63 @@
+// Microchip MCP9808 temperature sensor on I2C
+use super::bus::{I2cBus, I2cDevice};
+use crate::sensors::{interface::Sensor, Channel, SensorError, Unit};
+
+// A0-A2 pins select one of 8 addresses from 0x18
+pub const MCP9808_ADDRESSES: [u8; 8] = [0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f];
+const MANUFACTURER_ID: u16 = 0x0054;
+const DEVICE_ID: u8 = 0x04;
+
+// Registers
+const REG_AMBIENT_TEMPERATURE: u8 = 0x05;
+const REG_MANUFACTURER_ID: u8 = 0x06;
+const REG_DEVICE_ID: u8 = 0x07;
+
+// Ambient temperature register layout
+const TEMPERATURE_MASK: u16 = 0x0fff;
+const SIGN: u16 = 1 << 12;
+
+static CHANNELS: [Channel; 1] = [Channel {
+    name: "temperature",
+    unit: Unit::Celsius,
+}];
+
+pub struct Mcp9808<'a, B: I2cBus> {
+    device: I2cDevice<'a, B>,
+}
+
+impl<'a, B: I2cBus> Mcp9808<'a, B> {
+    // Chip powers up in continuous conversion, only ids are checked
+    pub fn probe(bus: &'a B, address: u8) -> Result<Self, SensorError> {
+        let device = I2cDevice::new(bus, address);
+        if device.read_reg16(REG_MANUFACTURER_ID)? != MANUFACTURER_ID {
+            return Err(SensorError::WrongDevice);
+        }
+        // High byte is device id, low byte revision
+        if (device.read_reg16(REG_DEVICE_ID)? >> 8) as u8 != DEVICE_ID {
+            return Err(SensorError::WrongDevice);
+        }
+        Ok(Self { device })
+    }
+}
+
+impl<'a, B: I2cBus> Sensor for Mcp9808<'a, B> {
+    fn name(&self) -> &'static str {
+        "mcp9808"
+    }
+    fn channels(&self) -> &'static [Channel] {
+        &CHANNELS
+    }
+    fn sample(&self, values: &mut [f32]) -> Result<(), SensorError> {
+        if values.is_empty() {
+            return Err(SensorError::BufferTooSmall);
+        }
+        // Bits 13-15 are alert flags, temperature is 1/16 C steps
+        let raw = self.device.read_reg16(REG_AMBIENT_TEMPERATURE)?;
+        let mut temperature = (raw & TEMPERATURE_MASK) as f32 / 16.0;
+        if raw & SIGN != 0 {
+            temperature -= 256.0;
+        }
+        values[0] = temperature;
+        Ok(())
+    }
+}
@@ -1,2 +1,4 @@
+pub mod clock;
 pub mod console;
 pub mod cpu;
+pub mod driver;
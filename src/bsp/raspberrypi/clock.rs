@@ -0,0 +1,42 @@
+use crate::bsp::bcm::{
+    bcm2711_cm::{ClockGenerator, OSCILLATOR_RATE, PLLD_PER_RATE},
+    bcm2711_mailbox::FirmwareClock,
+    clock_manager, mailbox, uart_clock,
+};
+
+// Used when firmware doesn't answer
+const DEFAULT_CORE_RATE: u32 = 150_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClockId {
+    Oscillator,
+    PlldPer,
+    // VPU clock, feeds I2C and SPI controllers
+    Core,
+    Uart,
+    Gp0,
+    Gp1,
+    Gp2,
+    Pcm,
+    Pwm,
+}
+
+// Rate in Hz, 0 when clock is stopped or its source is unknown
+pub fn rate_of(id: ClockId) -> u32 {
+    match id {
+        ClockId::Oscillator => OSCILLATOR_RATE,
+        ClockId::PlldPer => PLLD_PER_RATE,
+        // Core and UART clocks are owned by firmware
+        ClockId::Core => mailbox()
+            .get_clock_rate(FirmwareClock::Core)
+            .unwrap_or(DEFAULT_CORE_RATE),
+        ClockId::Uart => mailbox()
+            .get_clock_rate(FirmwareClock::Uart)
+            .unwrap_or(uart_clock()),
+        ClockId::Gp0 => clock_manager().rate(ClockGenerator::Gp0),
+        ClockId::Gp1 => clock_manager().rate(ClockGenerator::Gp1),
+        ClockId::Gp2 => clock_manager().rate(ClockGenerator::Gp2),
+        ClockId::Pcm => clock_manager().rate(ClockGenerator::Pcm),
+        ClockId::Pwm => clock_manager().rate(ClockGenerator::Pwm),
+    }
+}
@@ -11,6 +11,11 @@ struct QEMUOutput {
 }
 static QEMU_OUTPUT: QEMUOutput = QEMUOutput::new();
 static mut UART_CONSOLE: Option<&mut Uart> = None;
+// Additional output only consoles, like framebuffer
+const MAX_CONSOLE_SINKS: usize = 4;
+static mut CONSOLE_SINKS: [Option<&'static dyn console::interface::All>; MAX_CONSOLE_SINKS] =
+    [None; MAX_CONSOLE_SINKS];
+static CONSOLE_MUX: ConsoleMux = ConsoleMux;
 
 impl QEMUOutputInner {
     const fn new() -> Self {
@@ -43,6 +48,10 @@ impl QEMUOutput {
     }
 }
 pub fn console() -> &'static dyn console::interface::All {
+    &CONSOLE_MUX
+}
+
+fn uart_console() -> &'static dyn console::interface::All {
     unsafe {
         if let Some(uart) = &UART_CONSOLE {
             return uart;
@@ -50,6 +59,33 @@ pub fn console() -> &'static dyn console::interface::All {
         panic!("No initialized uart console!")
     }
 }
+
+// Writes go to uart and every registered sink, reads come only from uart
+struct ConsoleMux;
+
+impl console::interface::Write for ConsoleMux {
+    fn write_fmt(&self, args: core::fmt::Arguments) -> fmt::Result {
+        let result = uart_console().write_fmt(args);
+        unsafe {
+            for sink in CONSOLE_SINKS.iter().flatten() {
+                // Broken sink should not silence the uart
+                let _ = sink.write_fmt(args);
+            }
+        }
+        result
+    }
+}
+impl console::interface::Read for ConsoleMux {
+    fn read_char(&self) -> Option<char> {
+        uart_console().read_char()
+    }
+}
+impl console::interface::Statistics for ConsoleMux {
+    fn chars_written(&self) -> usize {
+        uart_console().chars_written()
+    }
+}
+impl console::interface::All for ConsoleMux {}
 use synchronization::interface::Mutex;
 
 impl console::interface::Write for QEMUOutput {
@@ -62,6 +98,7 @@ impl console::interface::Statistics for QEMUOutput {
         self.inner.lock(|inner| inner.chars_written)
     }
 }
+impl console::interface::Read for QEMUOutput {}
 impl console::interface::All for QEMUOutput {}
 
 //_____________________________________________________________
@@ -74,3 +111,16 @@ pub fn register_console(driver: &'static mut Uart) {
         UART_CONSOLE = Some(driver);
     }
 }
+
+// Returns false when there is no free slot left
+pub fn register_console_sink(sink: &'static dyn console::interface::All) -> bool {
+    unsafe {
+        match CONSOLE_SINKS.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(sink);
+                true
+            }
+            None => false,
+        }
+    }
+}
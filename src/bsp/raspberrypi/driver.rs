@@ -0,0 +1,44 @@
+use crate::bsp::bcm::{bcm2711_gpio::GPIOError, bcm2711_irq, I2cPinError, PwmError};
+use crate::driver::DriverError;
+use core::fmt;
+
+// Init failures of BCM2711 drivers, carried by DriverError::Device
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeviceError {
+    Gpio(GPIOError),
+    I2cPins(I2cPinError),
+    Pwm(PwmError),
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceError::Gpio(error) => write!(f, "{}", error),
+            DeviceError::I2cPins(error) => write!(f, "{}", error),
+            DeviceError::Pwm(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<GPIOError> for DriverError {
+    fn from(error: GPIOError) -> Self {
+        DriverError::Device(DeviceError::Gpio(error))
+    }
+}
+
+impl From<I2cPinError> for DriverError {
+    fn from(error: I2cPinError) -> Self {
+        DriverError::Device(DeviceError::I2cPins(error))
+    }
+}
+
+impl From<PwmError> for DriverError {
+    fn from(error: PwmError) -> Self {
+        DriverError::Device(DeviceError::Pwm(error))
+    }
+}
+
+// Drivers give VideoCore interruption numbers
+pub unsafe fn enable_irq(irq: u32) {
+    bcm2711_irq::enable_vc_irq(irq)
+}
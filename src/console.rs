@@ -5,12 +5,18 @@ pub mod interface {
     pub trait Write {
         fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result;
     }
+    pub trait Read {
+        // None when console has no input side
+        fn read_char(&self) -> Option<char> {
+            None
+        }
+    }
     pub trait Statistics {
         fn chars_written(&self) -> usize {
             0
         }
     }
-    pub trait All: Write + Statistics {}
+    pub trait All: Write + Read + Statistics {}
 }
 
 pub fn console() -> &'static dyn interface::All {
@@ -1,2 +1,4 @@
 #[path = "../_arch/aarch64/cpu/boot.rs"]
 pub mod arch_boot;
+
+pub use arch_boot::dtb_address;
@@ -1,2 +1,4 @@
-use core::arch::global_asm;
-global_asm!(include_str!("../_arch/aarch64/cpu/exceptions.s"));
+#[path = "../_arch/aarch64/cpu/exceptions.rs"]
+mod arch_exceptions;
+
+pub use arch_exceptions::{init_exception_handling, unmask_irq};
@@ -0,0 +1,217 @@
+use crate::cpu::boot::dtb_address;
+use fdt::{node::FdtNode, Fdt, FdtError};
+
+// Enough for BCM2711 soc node, which has 3 windows
+const MAX_RANGES: usize = 8;
+// Interrupt specifier types of GIC binding
+const GIC_SPI: u32 = 0;
+const GIC_PPI: u32 = 1;
+const GIC_SPI_BASE: u32 = 32;
+const GIC_PPI_BASE: u32 = 16;
+
+// Tree firmware passed at boot, it stays where firmware put it
+pub fn device_tree() -> Result<Fdt<'static>, FdtError> {
+    unsafe { Fdt::from_ptr(dtb_address() as *const u8) }
+}
+
+// Big endian value made of given number of 32 bit cells, and the rest
+fn read_cells(data: &[u8], cells: usize) -> Option<(u64, &[u8])> {
+    if data.len() < cells * 4 {
+        return None;
+    }
+    let (value, rest) = data.split_at(cells * 4);
+    let value = value.chunks_exact(4).fold(0u64, |value, cell| {
+        (value << 32) | u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]]) as u64
+    });
+    Some((value, rest))
+}
+
+fn property_u32(node: FdtNode, name: &str) -> Option<u32> {
+    read_cells(node.property(name)?.value, 1).map(|(value, _)| value as u32)
+}
+
+#[derive(Clone, Copy)]
+struct Range {
+    child: u64,
+    parent: u64,
+    size: u64,
+}
+
+// Address space children of a node live in
+struct Bus<'p> {
+    address_cells: usize,
+    size_cells: usize,
+    // None maps child addresses to parent ones unchanged
+    ranges: Option<([Range; MAX_RANGES], usize)>,
+    interrupt_parent: Option<u32>,
+    parent: Option<&'p Bus<'p>>,
+}
+
+impl<'p> Bus<'p> {
+    fn root(node: FdtNode) -> Self {
+        let cells = node.cell_sizes();
+        Self {
+            address_cells: cells.address_cells,
+            size_cells: cells.size_cells,
+            ranges: None,
+            interrupt_parent: property_u32(node, "interrupt-parent"),
+            parent: None,
+        }
+    }
+
+    // Bus of node children, its ranges lead to address space of this bus
+    fn child(&'p self, node: FdtNode) -> Self {
+        let cells = node.cell_sizes();
+        let ranges = node
+            .property("ranges")
+            .filter(|ranges| !ranges.value.is_empty())
+            .map(|ranges| {
+                let mut table = [Range {
+                    child: 0,
+                    parent: 0,
+                    size: 0,
+                }; MAX_RANGES];
+                let mut count = 0;
+                let mut data = ranges.value;
+                while count < MAX_RANGES {
+                    let Some((child, rest)) = read_cells(data, cells.address_cells) else {
+                        break;
+                    };
+                    let Some((parent, rest)) = read_cells(rest, self.address_cells) else {
+                        break;
+                    };
+                    let Some((size, rest)) = read_cells(rest, cells.size_cells) else {
+                        break;
+                    };
+                    table[count] = Range {
+                        child,
+                        parent,
+                        size,
+                    };
+                    count += 1;
+                    data = rest;
+                }
+                (table, count)
+            });
+        Self {
+            address_cells: cells.address_cells,
+            size_cells: cells.size_cells,
+            ranges,
+            interrupt_parent: property_u32(node, "interrupt-parent").or(self.interrupt_parent),
+            parent: Some(self),
+        }
+    }
+
+    // Walks up to root, e.g. 0x7e20_1000 on soc bus is 0xfe20_1000 for ARM.
+    // None when address is outside every window
+    fn translate(&self, address: u64) -> Option<u64> {
+        let Some(parent) = self.parent else {
+            return Some(address);
+        };
+        let address = match &self.ranges {
+            Some((ranges, count)) => ranges[..*count]
+                .iter()
+                .find(|range| (range.child..range.child + range.size).contains(&address))
+                .map(|range| range.parent + (address - range.child))?,
+            None => address,
+        };
+        parent.translate(address)
+    }
+}
+
+pub struct DeviceNode<'b, 'a> {
+    pub node: FdtNode<'b, 'a>,
+    // First reg window as ARM physical address and size
+    pub reg: Option<(usize, usize)>,
+    // First interruption as GIC interruption ID
+    pub interrupt: Option<u32>,
+    // Rate of first input clock, known only for fixed clocks
+    pub clock_frequency: Option<u32>,
+}
+
+impl<'b, 'a> DeviceNode<'b, 'a> {
+    fn new(fdt: &'b Fdt<'a>, node: FdtNode<'b, 'a>, bus: &Bus) -> Self {
+        let reg = node.property("reg").and_then(|reg| {
+            let (address, rest) = read_cells(reg.value, bus.address_cells)?;
+            let (size, _) = read_cells(rest, bus.size_cells)?;
+            Some((bus.translate(address)? as usize, size as usize))
+        });
+        let interrupt_parent = property_u32(node, "interrupt-parent").or(bus.interrupt_parent);
+        let interrupt = interrupt_parent
+            .and_then(|phandle| fdt.find_phandle(phandle))
+            .and_then(|controller| controller.interrupt_cells())
+            .zip(node.property("interrupts"))
+            .and_then(|(cells, interrupts)| Self::interrupt_id(interrupts.value, cells));
+        let clock_frequency = property_u32(node, "clocks")
+            .and_then(|phandle| fdt.find_phandle(phandle))
+            .and_then(|clock| property_u32(clock, "clock-frequency"));
+        Self {
+            node,
+            reg,
+            interrupt,
+            clock_frequency,
+        }
+    }
+
+    // Three cell specifier is GIC one: type, number and flags
+    fn interrupt_id(data: &[u8], cells: usize) -> Option<u32> {
+        let (first, rest) = read_cells(data, 1)?;
+        if cells != 3 {
+            return Some(first as u32);
+        }
+        let (number, _) = read_cells(rest, 1)?;
+        match first as u32 {
+            GIC_SPI => Some(GIC_SPI_BASE + number as u32),
+            GIC_PPI => Some(GIC_PPI_BASE + number as u32),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.node.name
+    }
+
+    pub fn property_u32(&self, name: &str) -> Option<u32> {
+        property_u32(self.node, name)
+    }
+}
+
+fn is_enabled(node: FdtNode) -> bool {
+    match node.property("status").and_then(|status| status.as_str()) {
+        Some(status) => status == "okay" || status == "ok",
+        None => true,
+    }
+}
+
+fn walk<'b, 'a>(
+    fdt: &'b Fdt<'a>,
+    node: FdtNode<'b, 'a>,
+    bus: &Bus,
+    compatible: &str,
+    f: &mut dyn FnMut(&DeviceNode<'b, 'a>),
+) {
+    for child in node.children() {
+        // Disabled node takes its whole subtree with it
+        if !is_enabled(child) {
+            continue;
+        }
+        if child
+            .compatible()
+            .is_some_and(|list| list.all().any(|entry| entry == compatible))
+        {
+            f(&DeviceNode::new(fdt, child, bus));
+        }
+        walk(fdt, child, &bus.child(child), compatible, f);
+    }
+}
+
+// Calls f for every enabled node with given compatible string, in tree order
+pub fn for_each_compatible<'b, 'a>(
+    fdt: &'b Fdt<'a>,
+    compatible: &str,
+    mut f: impl FnMut(&DeviceNode<'b, 'a>),
+) {
+    if let Some(root) = fdt.find_node("/") {
+        walk(fdt, root, &Bus::root(root), compatible, &mut f);
+    }
+}
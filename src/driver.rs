@@ -0,0 +1,197 @@
+use crate::bsp::driver::{enable_irq, DeviceError};
+use crate::device_tree::{self, DeviceNode};
+use crate::println;
+use core::fmt;
+use fdt::Fdt;
+
+const MAX_DRIVERS: usize = 24;
+
+static mut DRIVER_MANAGER: DriverManager = DriverManager::new();
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DriverError {
+    // No free slot left in driver manager
+    RegistryFull,
+    // Failure of the device itself, kinds of it are up to BSP
+    Device(DeviceError),
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DriverError::RegistryFull => write!(f, "driver registry is full"),
+            DriverError::Device(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+pub mod interface {
+    use super::DriverError;
+    pub trait DeviceDriver {
+        fn name(&self) -> &'static str;
+        // Device tree compatible string of handled hardware
+        fn compatible(&self) -> &'static str;
+        unsafe fn init(&self) -> Result<(), DriverError>;
+        // Interruption enabled once init succeeds, BSP defines numbering
+        fn irq_number(&self) -> Option<u32> {
+            None
+        }
+    }
+}
+
+// Runs after driver init succeeded and its interruption is enabled
+pub type PostInitCallback = unsafe fn() -> Result<(), DriverError>;
+
+#[derive(Clone, Copy)]
+pub struct DeviceDriverDescriptor {
+    driver: &'static dyn interface::DeviceDriver,
+    post_init: Option<PostInitCallback>,
+    // Taken from device tree, replaces the one driver knows
+    irq_number: Option<u32>,
+}
+
+impl DeviceDriverDescriptor {
+    pub fn new(
+        driver: &'static dyn interface::DeviceDriver,
+        post_init: Option<PostInitCallback>,
+    ) -> Self {
+        Self {
+            driver,
+            post_init,
+            irq_number: None,
+        }
+    }
+    pub fn with_irq(mut self, irq_number: Option<u32>) -> Self {
+        self.irq_number = irq_number.or(self.irq_number);
+        self
+    }
+    // Tree one is used only by drivers that handle an interruption
+    fn irq_number(&self) -> Option<u32> {
+        self.driver
+            .irq_number()
+            .map(|irq| self.irq_number.unwrap_or(irq))
+    }
+}
+
+// Driver for nodes with given compatible string. Probe configures
+// driver instance from the node, None when there is no instance for it
+pub struct DeviceTreeMatch {
+    pub compatible: &'static str,
+    pub probe: unsafe fn(&DeviceNode) -> Option<DeviceDriverDescriptor>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DriverState {
+    Registered,
+    Ready,
+    Failed(DriverError),
+}
+
+pub struct DriverManager {
+    drivers: [Option<(DeviceDriverDescriptor, DriverState)>; MAX_DRIVERS],
+    // Drivers turned away because registry was full
+    unregistered: usize,
+}
+
+impl DriverManager {
+    const fn new() -> Self {
+        Self {
+            drivers: [None; MAX_DRIVERS],
+            unregistered: 0,
+        }
+    }
+
+    // Drivers are initialized in registration order. Rejected ones are
+    // also reported by init_drivers, as console may not be up yet
+    pub fn register_driver(
+        &mut self,
+        descriptor: DeviceDriverDescriptor,
+    ) -> Result<(), DriverError> {
+        let Some(slot) = self.drivers.iter_mut().find(|slot| slot.is_none()) else {
+            self.unregistered += 1;
+            return Err(DriverError::RegistryFull);
+        };
+        *slot = Some((descriptor, DriverState::Registered));
+        Ok(())
+    }
+
+    // Registers drivers in order of match table, so it decides init order
+    // as well. Returns number of nodes that got a driver
+    pub unsafe fn probe_device_tree(&mut self, fdt: &Fdt, matches: &[DeviceTreeMatch]) -> usize {
+        let mut probed = 0;
+        for entry in matches {
+            device_tree::for_each_compatible(fdt, entry.compatible, |node| {
+                let Some(descriptor) = (entry.probe)(node) else {
+                    return;
+                };
+                if self.register_driver(descriptor).is_ok() {
+                    probed += 1;
+                }
+            });
+        }
+        probed
+    }
+
+    unsafe fn init_driver(descriptor: &DeviceDriverDescriptor) -> Result<(), DriverError> {
+        descriptor.driver.init()?;
+        if let Some(irq) = descriptor.irq_number() {
+            enable_irq(irq);
+        }
+        match descriptor.post_init {
+            Some(callback) => callback(),
+            None => Ok(()),
+        }
+    }
+
+    // Failing driver is reported and skipped, others still come up.
+    // Returns number of drivers that failed
+    pub unsafe fn init_drivers(&mut self) -> usize {
+        let mut failed = 0;
+        for (index, (descriptor, state)) in self.drivers.iter_mut().flatten().enumerate() {
+            if *state != DriverState::Registered {
+                continue;
+            }
+            *state = match Self::init_driver(descriptor) {
+                Ok(()) => DriverState::Ready,
+                Err(error) => DriverState::Failed(error),
+            };
+            // First driver is the console, so reports start once it is up
+            match state {
+                DriverState::Failed(error) => {
+                    failed += 1;
+                    println!(
+                        "  {:>2}. {} ({}) failed: {}",
+                        index + 1,
+                        descriptor.driver.name(),
+                        descriptor.driver.compatible(),
+                        error
+                    );
+                }
+                _ => println!(
+                    "  {:>2}. {} ({})",
+                    index + 1,
+                    descriptor.driver.name(),
+                    descriptor.driver.compatible()
+                ),
+            }
+        }
+        if self.unregistered > 0 {
+            println!(
+                "{} drivers not registered: {}",
+                self.unregistered,
+                DriverError::RegistryFull
+            );
+        }
+        failed
+    }
+
+    pub fn for_each_driver(&self, mut f: impl FnMut(&dyn interface::DeviceDriver, DriverState)) {
+        for (descriptor, state) in self.drivers.iter().flatten() {
+            f(descriptor.driver, *state)
+        }
+    }
+}
+
+pub fn driver_manager() -> &'static mut DriverManager {
+    unsafe { &mut DRIVER_MANAGER }
+}
@@ -4,13 +4,21 @@
 mod bsp;
 mod console;
 mod cpu;
+mod device_tree;
+mod driver;
 mod panic_wait;
 mod print;
+mod sensors;
+mod shell;
 mod synchronization;
+mod time;
 
 use bsp::bcm::init_drivers;
 
 pub fn kernel_init() -> ! {
-    unsafe { init_drivers() }
-    panic!("STOP");
+    unsafe {
+        cpu::exceptions::init_exception_handling();
+        init_drivers()
+    }
+    shell::run()
 }
@@ -7,7 +7,7 @@ pub fn _print(args: fmt::Arguments) {
 
 #[macro_export]
 macro_rules! print {
-    ($($arg:tt)*) => {$crate::print::_print(format_args!($($arg)*));};
+    ($($arg:tt)*) => ($crate::print::_print(format_args!($($arg)*)));
 }
 
 #[macro_export]
@@ -15,5 +15,5 @@ macro_rules! println {
     () => ({
         $crate::print!("\n")
     });
-    ($($arg:tt)*) => ({$crate::print::_print(format_args!($($arg)*));});
+    ($($arg:tt)*) => ({$crate::print::_print(format_args!("{}\n", format_args!($($arg)*)));});
 }
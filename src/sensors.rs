@@ -0,0 +1,82 @@
+use crate::bsp::bus::I2cError;
+use core::fmt;
+
+const MAX_SENSORS: usize = 8;
+// Most channels any sensor has, enough for sample buffers on stack
+pub const MAX_CHANNELS: usize = 4;
+
+static mut SENSORS: [Option<&'static dyn interface::Sensor>; MAX_SENSORS] = [None; MAX_SENSORS];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Unit {
+    Celsius,
+    Hectopascal,
+    RelativeHumidity,
+}
+
+impl Unit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Celsius => "C",
+            Unit::Hectopascal => "hPa",
+            Unit::RelativeHumidity => "%RH",
+        }
+    }
+}
+
+pub struct Channel {
+    pub name: &'static str,
+    pub unit: Unit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SensorError {
+    I2c(I2cError),
+    // Chip at the address is not the expected one
+    WrongDevice,
+    // Buffer has fewer slots than sensor has channels
+    BufferTooSmall,
+}
+
+impl fmt::Display for SensorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SensorError::I2c(error) => write!(f, "{}", error),
+            SensorError::WrongDevice => write!(f, "unexpected device id"),
+            SensorError::BufferTooSmall => write!(f, "sample buffer too small"),
+        }
+    }
+}
+
+impl From<I2cError> for SensorError {
+    fn from(error: I2cError) -> Self {
+        SensorError::I2c(error)
+    }
+}
+
+pub mod interface {
+    use super::{Channel, SensorError};
+    pub trait Sensor {
+        fn name(&self) -> &'static str;
+        fn channels(&self) -> &'static [Channel];
+        // Writes one value per channel, in order of channels()
+        fn sample(&self, values: &mut [f32]) -> Result<(), SensorError>;
+    }
+}
+
+// Returns false when there is no free slot left
+pub fn register_sensor(sensor: &'static dyn interface::Sensor) -> bool {
+    unsafe {
+        match SENSORS.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(sensor);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub fn sensors() -> impl Iterator<Item = &'static dyn interface::Sensor> {
+    unsafe { SENSORS.iter().flatten().copied() }
+}
@@ -0,0 +1,157 @@
+use crate::{console, cpu, print, println};
+
+mod bscslave;
+mod date;
+mod drivers;
+mod eeprom;
+mod gpio;
+mod i2c;
+mod onewire;
+mod sensors;
+
+const LINE_LENGTH: usize = 128;
+const MAX_ARGS: usize = 8;
+
+pub struct Command {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub handler: fn(&[&str]),
+}
+
+static COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        usage: "help",
+        handler: help,
+    },
+    Command {
+        name: "drivers",
+        usage: "drivers",
+        handler: drivers::drivers,
+    },
+    Command {
+        name: "pins",
+        usage: "pins",
+        handler: gpio::pins,
+    },
+    Command {
+        name: "i2cdetect",
+        usage: "i2cdetect <bus>",
+        handler: i2c::i2cdetect,
+    },
+    Command {
+        name: "i2cget",
+        usage: "i2cget <bus> <addr> <reg>",
+        handler: i2c::i2cget,
+    },
+    Command {
+        name: "i2cset",
+        usage: "i2cset <bus> <addr> <reg> <val>",
+        handler: i2c::i2cset,
+    },
+    Command {
+        name: "i2cdump",
+        usage: "i2cdump <bus> <addr>",
+        handler: i2c::i2cdump,
+    },
+    Command {
+        name: "bscslave",
+        usage: "bscslave <addr> <seconds>",
+        handler: bscslave::bscslave,
+    },
+    Command {
+        name: "eeprom",
+        usage: "eeprom <bus> <addr> <part> [offset] [length]",
+        handler: eeprom::eeprom,
+    },
+    Command {
+        name: "date",
+        usage: "date [-s YYYY-MM-DD HH:MM:SS]",
+        handler: date::date,
+    },
+    Command {
+        name: "sensors",
+        usage: "sensors",
+        handler: sensors::sensors,
+    },
+    Command {
+        name: "w1scan",
+        usage: "w1scan [pin]",
+        handler: onewire::w1scan,
+    },
+];
+
+pub fn run() -> ! {
+    let mut line = [0u8; LINE_LENGTH];
+    loop {
+        print!("> ");
+        let length = read_line(&mut line);
+        match core::str::from_utf8(&line[..length]) {
+            Ok(command) => execute(command),
+            Err(_) => println!("Invalid characters in command"),
+        }
+    }
+}
+
+fn read_line(line: &mut [u8]) -> usize {
+    let mut length = 0;
+    loop {
+        let Some(c) = console::console().read_char() else {
+            println!("Console has no input, shell stopped");
+            cpu::wait_forever()
+        };
+        match c {
+            '\n' => {
+                println!();
+                return length;
+            }
+            // Backspace and delete
+            '\x08' | '\x7f' => {
+                if length > 0 {
+                    length -= 1;
+                    print!("\x08 \x08");
+                }
+            }
+            _ if c.is_ascii() && !c.is_ascii_control() && length < line.len() => {
+                line[length] = c as u8;
+                length += 1;
+                print!("{}", c);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn execute(line: &str) {
+    let mut args: [&str; MAX_ARGS] = [""; MAX_ARGS];
+    let mut count = 0;
+    for arg in line.split_ascii_whitespace() {
+        if count == MAX_ARGS {
+            println!("Too many arguments");
+            return;
+        }
+        args[count] = arg;
+        count += 1;
+    }
+    if count == 0 {
+        return;
+    }
+    match COMMANDS.iter().find(|command| command.name == args[0]) {
+        Some(command) => (command.handler)(&args[1..count]),
+        None => println!("Unknown command: {}", args[0]),
+    }
+}
+
+fn help(_args: &[&str]) {
+    for command in COMMANDS {
+        println!("  {}", command.usage);
+    }
+}
+
+// Parses decimal or 0x prefixed hexadecimal number
+pub fn parse_number(arg: &str) -> Option<u32> {
+    match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => arg.parse().ok(),
+    }
+}
@@ -0,0 +1,41 @@
+use super::i2c::byte_arg;
+use super::parse_number;
+use crate::bsp::bcm::{bsc_slave, RegisterFile, SlaveMode};
+use crate::{println, time};
+use core::time::Duration;
+
+// Registers master sees, they keep values between runs
+static REGISTERS: RegisterFile<256> = RegisterFile::new(None);
+
+pub fn bscslave(args: &[&str]) {
+    if args.len() != 2 {
+        println!("Usage: bscslave <addr> <seconds>");
+        return;
+    }
+    let Some(address) = byte_arg(args[0], "address", 0x7f) else {
+        return;
+    };
+    let Some(seconds) = parse_number(args[1]) else {
+        println!("Invalid time: {}", args[1]);
+        return;
+    };
+    let slave = bsc_slave();
+    // Fails while SPI0 holds the pins
+    if let Err(error) = unsafe { slave.enable(SlaveMode::I2c { address }, &REGISTERS) } {
+        println!("Cannot enable slave: {}", error);
+        return;
+    }
+    println!(
+        "Serving 256 registers at 0x{:02x} for {} s",
+        address, seconds
+    );
+    let end = time::uptime() + Duration::from_secs(seconds as u64);
+    let mut transfers = 0;
+    while time::uptime() < end {
+        if slave.poll() {
+            transfers += 1;
+        }
+    }
+    unsafe { slave.disable() };
+    println!("{} transfers served", transfers);
+}
@@ -0,0 +1,47 @@
+use crate::println;
+use crate::time::{self, DateTime};
+
+// Splits "a<separator>b<separator>c" into three numbers
+fn triple(arg: &str, separator: char) -> Option<(u16, u8, u8)> {
+    let mut parts = arg.split(separator);
+    let first = parts.next()?.parse().ok()?;
+    let second = parts.next()?.parse().ok()?;
+    let third = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some(_) => None,
+        None => Some((first, second, third)),
+    }
+}
+
+fn parse_date_time(date: &str, clock: &str) -> Option<DateTime> {
+    let (year, month, day) = triple(date, '-')?;
+    let (hour, minute, second) = triple(clock, ':')?;
+    let time = DateTime {
+        year,
+        month,
+        day,
+        hour: u8::try_from(hour).ok()?,
+        minute,
+        second,
+    };
+    time.is_valid().then_some(time)
+}
+
+pub fn date(args: &[&str]) {
+    match args {
+        [] => match time::wall_clock() {
+            Some(now) => println!("{} UTC", DateTime::from_unix(now.as_secs())),
+            None => println!("Wall clock not set"),
+        },
+        ["-s", date, clock] => {
+            let Some(time) = parse_date_time(date, clock) else {
+                println!("Invalid date: {} {}", date, clock);
+                return;
+            };
+            if let Err(error) = time::set_wall_clock(&time) {
+                println!("RTC not updated: {}", error);
+            }
+        }
+        _ => println!("Usage: date [-s YYYY-MM-DD HH:MM:SS]"),
+    }
+}
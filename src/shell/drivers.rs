@@ -0,0 +1,17 @@
+use crate::driver::{driver_manager, DriverState};
+use crate::println;
+
+pub fn drivers(_args: &[&str]) {
+    println!("NAME     COMPATIBLE            STATE");
+    driver_manager().for_each_driver(|driver, state| {
+        let name = driver.name();
+        let compatible = driver.compatible();
+        match state {
+            DriverState::Registered => println!("{:<8} {:<21} registered", name, compatible),
+            DriverState::Ready => println!("{:<8} {:<21} ready", name, compatible),
+            DriverState::Failed(error) => {
+                println!("{:<8} {:<21} failed: {}", name, compatible, error)
+            }
+        }
+    });
+}
@@ -0,0 +1,78 @@
+use super::i2c::{bus_arg, byte_arg};
+use super::parse_number;
+use crate::bsp::eeprom24x::{Eeprom24x, Eeprom24xPart, EEPROM_24X_PARTS};
+use crate::{print, println};
+
+const DEFAULT_LENGTH: usize = 256;
+const BYTES_PER_LINE: usize = 16;
+
+fn usize_arg(arg: Option<&&str>, name: &str, default: usize) -> Option<usize> {
+    let Some(arg) = arg else {
+        return Some(default);
+    };
+    match parse_number(arg) {
+        Some(value) => Some(value as usize),
+        None => {
+            println!("Invalid {}: {}", name, arg);
+            None
+        }
+    }
+}
+
+pub fn eeprom(args: &[&str]) {
+    if !(3..=5).contains(&args.len()) {
+        println!("Usage: eeprom <bus> <addr> <part> [offset] [length]");
+        return;
+    }
+    let Some(bus) = bus_arg(args[0]) else { return };
+    let Some(addr) = byte_arg(args[1], "address", 0x7f) else {
+        return;
+    };
+    let Some(part) = Eeprom24xPart::from_name(args[2]) else {
+        print!("Unknown part {}, known:", args[2]);
+        for part in EEPROM_24X_PARTS {
+            print!(" {}", part.name());
+        }
+        println!();
+        return;
+    };
+    let Some(offset) = usize_arg(args.get(3), "offset", 0) else {
+        return;
+    };
+    let Some(length) = usize_arg(args.get(4), "length", DEFAULT_LENGTH) else {
+        return;
+    };
+    let eeprom = Eeprom24x::new(bus, addr, part);
+    // Dump never runs past end of memory
+    let end = (offset + length).min(eeprom.size());
+    let mut line = [0u8; BYTES_PER_LINE];
+    let mut position = offset;
+    while position < end {
+        let count = (end - position).min(BYTES_PER_LINE);
+        if let Err(error) = eeprom.read(position, &mut line[..count]) {
+            println!("Error at 0x{:04x}: {}", position, error);
+            return;
+        }
+        print!("{:04x}:", position);
+        for byte in &line[..count] {
+            print!(" {:02x}", byte);
+        }
+        for _ in count..BYTES_PER_LINE {
+            print!("   ");
+        }
+        print!("  ");
+        for byte in &line[..count] {
+            let c = *byte as char;
+            print!(
+                "{}",
+                if c.is_ascii_graphic() || c == ' ' {
+                    c
+                } else {
+                    '.'
+                }
+            );
+        }
+        println!();
+        position += count;
+    }
+}
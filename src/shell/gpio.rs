@@ -0,0 +1,7 @@
+use crate::bsp::bcm::bcm2711_gpio::for_each_claimed_pin;
+use crate::println;
+
+pub fn pins(_args: &[&str]) {
+    println!("PIN     OWNER");
+    for_each_claimed_pin(|pin, owner| println!("GPIO{:<3} {}", pin, owner));
+}
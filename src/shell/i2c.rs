@@ -0,0 +1,115 @@
+use super::parse_number;
+use crate::bsp::bcm::{i2c_bus, I2cError, I2C};
+use crate::{print, println};
+
+// Addresses below 0x03 and above 0x77 are reserved by I2C specification
+const FIRST_ADDRESS: u8 = 0x03;
+const LAST_ADDRESS: u8 = 0x77;
+
+pub(super) fn bus_arg(arg: &str) -> Option<&'static I2C> {
+    let bus = match parse_number(arg) {
+        Some(bus) => bus,
+        None => {
+            println!("Invalid bus number: {}", arg);
+            return None;
+        }
+    };
+    let driver = i2c_bus(bus);
+    if driver.is_none() {
+        println!("I2C bus {} is not initialized", bus);
+    }
+    driver
+}
+
+pub(super) fn byte_arg(arg: &str, name: &str, max: u8) -> Option<u8> {
+    match parse_number(arg) {
+        Some(value) if value <= max as u32 => Some(value as u8),
+        _ => {
+            println!("Invalid {}: {}", name, arg);
+            None
+        }
+    }
+}
+
+fn report_error(addr: u8, error: I2cError) {
+    println!("Error at 0x{:02x}: {}", addr, error);
+}
+
+pub fn i2cdetect(args: &[&str]) {
+    if args.len() != 1 {
+        println!("Usage: i2cdetect <bus>");
+        return;
+    }
+    let Some(bus) = bus_arg(args[0]) else { return };
+    println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f");
+    for row in (0u8..0x80).step_by(16) {
+        print!("{:02x}:", row);
+        for addr in row..row + 16 {
+            if !(FIRST_ADDRESS..=LAST_ADDRESS).contains(&addr) {
+                print!("   ");
+                continue;
+            }
+            match bus.probe(addr) {
+                Ok(()) => print!(" {:02x}", addr),
+                Err(I2cError::Nack) => print!(" --"),
+                // Slave held the clock, something is there but misbehaves
+                Err(I2cError::ClockStretchTimeout) => print!(" TO"),
+                Err(_) => print!(" ??"),
+            }
+        }
+        println!();
+    }
+}
+
+pub fn i2cget(args: &[&str]) {
+    if args.len() != 3 {
+        println!("Usage: i2cget <bus> <addr> <reg>");
+        return;
+    }
+    let Some(bus) = bus_arg(args[0]) else { return };
+    let Some(addr) = byte_arg(args[1], "address", LAST_ADDRESS) else { return };
+    let Some(register) = byte_arg(args[2], "register", u8::MAX) else { return };
+    match bus.read_register(addr, register) {
+        Ok(value) => println!("0x{:02x}", value),
+        Err(error) => report_error(addr, error),
+    }
+}
+
+pub fn i2cset(args: &[&str]) {
+    if args.len() != 4 {
+        println!("Usage: i2cset <bus> <addr> <reg> <val>");
+        return;
+    }
+    let Some(bus) = bus_arg(args[0]) else { return };
+    let Some(addr) = byte_arg(args[1], "address", LAST_ADDRESS) else { return };
+    let Some(register) = byte_arg(args[2], "register", u8::MAX) else { return };
+    let Some(value) = byte_arg(args[3], "value", u8::MAX) else { return };
+    if let Err(error) = bus.write_register(addr, register, value) {
+        report_error(addr, error);
+    }
+}
+
+pub fn i2cdump(args: &[&str]) {
+    if args.len() != 2 {
+        println!("Usage: i2cdump <bus> <addr>");
+        return;
+    }
+    let Some(bus) = bus_arg(args[0]) else { return };
+    let Some(addr) = byte_arg(args[1], "address", LAST_ADDRESS) else { return };
+    // Bail out early instead of printing a table full of errors
+    if let Err(error) = bus.probe(addr) {
+        report_error(addr, error);
+        return;
+    }
+    println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f");
+    for row in (0u16..0x100).step_by(16) {
+        print!("{:02x}:", row);
+        for register in row..row + 16 {
+            match bus.read_register(addr, register as u8) {
+                Ok(value) => print!(" {:02x}", value),
+                Err(_) => print!(" XX"),
+            }
+        }
+        println!();
+    }
+}
@@ -0,0 +1,46 @@
+use super::parse_number;
+use crate::bsp::bitbang::one_wire::SoftOneWire;
+use crate::bsp::bus::{OneWireBus, OneWireSearch};
+use crate::{print, println};
+
+// Pin w1-gpio overlay uses when none is given
+const DEFAULT_PIN: u32 = 4;
+
+pub fn w1scan(args: &[&str]) {
+    let pin = match args {
+        [] => DEFAULT_PIN,
+        [arg] => match parse_number(arg) {
+            Some(pin) => pin,
+            None => {
+                println!("Invalid pin: {}", arg);
+                return;
+            }
+        },
+        _ => {
+            println!("Usage: w1scan [pin]");
+            return;
+        }
+    };
+    // Pin is claimed only for the time of the scan
+    let bus = match SoftOneWire::new(pin, "w1scan") {
+        Ok(bus) => bus,
+        Err(error) => {
+            println!("Cannot use GPIO {}: {}", pin, error);
+            return;
+        }
+    };
+    let mut search = OneWireSearch::default();
+    let mut found = 0;
+    while let Some(rom) = bus.search_next(&mut search) {
+        // Same naming as Linux w1 devices, family code then serial number
+        print!("{:02x}-", rom[0]);
+        for byte in rom[1..7].iter().rev() {
+            print!("{:02x}", byte);
+        }
+        println!();
+        found += 1;
+    }
+    if found == 0 {
+        println!("No devices found on GPIO {}", pin);
+    }
+}
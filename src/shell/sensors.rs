@@ -0,0 +1,26 @@
+use crate::sensors::{self, MAX_CHANNELS};
+use crate::{print, println};
+
+pub fn sensors(args: &[&str]) {
+    if !args.is_empty() {
+        println!("Usage: sensors");
+        return;
+    }
+    let mut found = false;
+    for sensor in sensors::sensors() {
+        found = true;
+        let mut values = [0f32; MAX_CHANNELS];
+        print!("{}:", sensor.name());
+        if let Err(error) = sensor.sample(&mut values) {
+            println!(" {}", error);
+            continue;
+        }
+        for (channel, value) in sensor.channels().iter().zip(values) {
+            print!(" {} {:.2} {}", channel.name, value, channel.unit.symbol());
+        }
+        println!();
+    }
+    if !found {
+        println!("No sensors registered");
+    }
+}
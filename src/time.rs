@@ -0,0 +1,103 @@
+#[path = "_arch/aarch64/time.rs"]
+mod arch_time;
+mod calendar;
+
+use crate::bsp::bus::I2cError;
+use core::fmt;
+use core::time::Duration;
+
+pub use arch_time::{spin_for, uptime};
+pub use calendar::DateTime;
+
+// Wall clock seconds at given uptime, set from RTC or by hand
+struct WallClockBase {
+    unix: Duration,
+    uptime: Duration,
+}
+
+static mut WALL_CLOCK_BASE: Option<WallClockBase> = None;
+static mut RTC: Option<&'static dyn interface::RealTimeClock> = None;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RtcError {
+    I2c(I2cError),
+    // Oscillator was stopped, kept time is lost
+    ClockStopped,
+    // Registers hold no valid date or date is out of chip range
+    InvalidTime,
+}
+
+impl fmt::Display for RtcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RtcError::I2c(error) => write!(f, "{}", error),
+            RtcError::ClockStopped => write!(f, "rtc oscillator stopped, time lost"),
+            RtcError::InvalidTime => write!(f, "invalid time"),
+        }
+    }
+}
+
+impl From<I2cError> for RtcError {
+    fn from(error: I2cError) -> Self {
+        RtcError::I2c(error)
+    }
+}
+
+pub mod interface {
+    use super::{DateTime, RtcError};
+    pub trait RealTimeClock {
+        fn name(&self) -> &'static str;
+        fn read_time(&self) -> Result<DateTime, RtcError>;
+        fn set_time(&self, time: &DateTime) -> Result<(), RtcError>;
+    }
+}
+
+// Wall clock follows RTC from now on, RTC is read once and
+// monotonic timer counts from there
+pub fn register_rtc(rtc: &'static dyn interface::RealTimeClock) -> Result<(), RtcError> {
+    unsafe { RTC = Some(rtc) }
+    sync_wall_clock()
+}
+
+pub fn rtc() -> Option<&'static dyn interface::RealTimeClock> {
+    unsafe { RTC }
+}
+
+// Re-reads RTC, sub-second part of it is not visible so wall
+// clock may lag by up to a second
+pub fn sync_wall_clock() -> Result<(), RtcError> {
+    let Some(rtc) = rtc() else { return Ok(()) };
+    let time = rtc.read_time()?;
+    set_base(&time);
+    Ok(())
+}
+
+fn set_base(time: &DateTime) {
+    unsafe {
+        WALL_CLOCK_BASE = Some(WallClockBase {
+            unix: Duration::from_secs(time.to_unix()),
+            uptime: uptime(),
+        })
+    }
+}
+
+// Time since Unix epoch, None until RTC is read or time is set
+pub fn wall_clock() -> Option<Duration> {
+    unsafe {
+        WALL_CLOCK_BASE
+            .as_ref()
+            .map(|base| base.unix + (uptime() - base.uptime))
+    }
+}
+
+// Kernel clock is set even when RTC write fails
+pub fn set_wall_clock(time: &DateTime) -> Result<(), RtcError> {
+    if !time.is_valid() {
+        return Err(RtcError::InvalidTime);
+    }
+    set_base(time);
+    match rtc() {
+        Some(rtc) => rtc.set_time(time),
+        None => Ok(()),
+    }
+}